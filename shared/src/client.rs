@@ -0,0 +1,232 @@
+//! Typed clients for the server's API, asynchronous here and blocking in [`blocking`]. Both fetch
+//! postcard and check the version in its [`envelope`] before decoding it.
+
+use std::fmt;
+use std::sync::Arc;
+
+use reqwest::{StatusCode, Url};
+use rustls::crypto::CryptoProvider;
+use rustls_platform_verifier::BuilderVerifierExt;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::envelope;
+use crate::fill;
+use crate::response::{GraphData, GraphList};
+
+pub mod blocking;
+
+/// How a graph is fetched. By default it comes as it was observed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct GraphOptions {
+    /// Fill in the gaps, and list the points that were made up in [`GraphData::filled`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill: Option<fill::Method>,
+    /// The longest gap, in missing points, that will be filled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_gap: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The base URL given to the client can't have the API's paths added to it.
+    InvalidBase(String),
+    /// The server could not be reached, or its response could not be read.
+    Http(reqwest::Error),
+    /// TLS could not be set up, e.g. as the platform's certificates could not be loaded.
+    Tls(rustls::Error),
+    /// The server answered with an error, and what it said about it.
+    Status { status: StatusCode, message: String },
+    /// The response was encoded with another version of the types, or is not what was asked for.
+    Decode(envelope::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidBase(reason) => write!(f, "invalid base URL {reason}"),
+            Error::Http(e) => write!(f, "{e}"),
+            Error::Tls(e) => write!(f, "error setting up TLS: {e}"),
+            Error::Status { status, message } => write!(f, "{status}: {message}"),
+            Error::Decode(e) => write!(f, "error decoding response: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            Error::Tls(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<rustls::Error> for Error {
+    fn from(e: rustls::Error) -> Self {
+        Error::Tls(e)
+    }
+}
+
+impl From<envelope::Error> for Error {
+    fn from(e: envelope::Error) -> Self {
+        Error::Decode(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Where the API's routes are, relative to the server's base URL.
+#[derive(Clone, Debug)]
+struct Routes {
+    base: Url,
+}
+
+impl Routes {
+    fn new(base: &str) -> Result<Self> {
+        let base = Url::parse(base).map_err(|e| Error::InvalidBase(format!("{base}: {e}")))?;
+        if base.cannot_be_a_base() {
+            return Err(Error::InvalidBase(format!("{base}: not a base")));
+        }
+        Ok(Routes { base })
+    }
+
+    /// The route made of `segments` under `/api/v1`, each escaped as need be.
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("checked it can be a base")
+            .pop_if_empty()
+            .extend(["api", "v1"])
+            .extend(segments);
+        url
+    }
+
+    fn list_graphs(&self) -> Url {
+        self.url(&["graphs"])
+    }
+
+    fn graph(&self, name: &str) -> Url {
+        self.url(&["graphs", name])
+    }
+
+    fn batch(&self) -> Url {
+        self.url(&["graphs", "batch"])
+    }
+}
+
+/// TLS for the clients' own HTTP clients, as reqwest is built without a crypto provider. Uses the
+/// process' default provider if one has been installed, or else ring as the server does, without
+/// installing it for everyone else.
+fn tls_config() -> Result<rustls::ClientConfig> {
+    let provider = CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(rustls::crypto::ring::default_provider()));
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_platform_verifier()?
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// The comma separated list `batch` expects.
+fn batch_query(names: &[&str]) -> [(&'static str, String); 1] {
+    [("names", names.join(","))]
+}
+
+fn decode<T: DeserializeOwned>(status: StatusCode, bytes: &[u8]) -> Result<T> {
+    if !status.is_success() {
+        return Err(Error::Status {
+            status,
+            message: String::from_utf8_lossy(bytes).into_owned(),
+        });
+    }
+    Ok(envelope::open(bytes)?)
+}
+
+/// An asynchronous client, which needs to be used within a Tokio runtime.
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    routes: Routes,
+}
+
+impl Client {
+    /// A client for the server at `base`, e.g. `http://localhost:8999`.
+    pub fn new(base: &str) -> Result<Self> {
+        let routes = Routes::new(base)?;
+        Ok(Client {
+            http: reqwest::Client::builder()
+                .tls_backend_preconfigured(tls_config()?)
+                .build()?,
+            routes,
+        })
+    }
+
+    /// Uses `http` to make requests, for its timeouts, proxies and so on. Building it panics
+    /// unless a rustls crypto provider has been installed, or it has been given TLS of its own.
+    pub fn with_http_client(http: reqwest::Client, base: &str) -> Result<Self> {
+        Ok(Client {
+            http,
+            routes: Routes::new(base)?,
+        })
+    }
+
+    /// Every graph the server has, without their points.
+    pub async fn list_graphs(&self) -> Result<GraphList> {
+        self.get(self.http.get(self.routes.list_graphs())).await
+    }
+
+    pub async fn graph(&self, name: &str, options: &GraphOptions) -> Result<GraphData> {
+        let request = self.http.get(self.routes.graph(name)).query(options);
+        self.get(request).await
+    }
+
+    /// Several graphs in one request, in the order they were asked for.
+    pub async fn graphs(&self, names: &[&str]) -> Result<Vec<GraphData>> {
+        let request = self
+            .http
+            .get(self.routes.batch())
+            .query(&batch_query(names));
+        self.get(request).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T> {
+        let response = request.send().await?;
+        let status = response.status();
+        decode(status, &response.bytes().await?)
+    }
+}
+
+#[test]
+fn test_routes() {
+    let routes = Routes::new("http://localhost:8999").unwrap();
+    assert_eq!(
+        routes.graph("Sea Level").as_str(),
+        "http://localhost:8999/api/v1/graphs/Sea%20Level"
+    );
+    let routes = Routes::new("https://example.com/climate/").unwrap();
+    assert_eq!(
+        routes.list_graphs().as_str(),
+        "https://example.com/climate/api/v1/graphs"
+    );
+    assert!(matches!(
+        Routes::new("mailto:someone@example.com"),
+        Err(Error::InvalidBase(_))
+    ));
+}
+
+#[test]
+fn test_new_leaves_crypto_provider() {
+    Client::new("https://example.com").unwrap();
+    blocking::Client::new("https://example.com").unwrap();
+    assert!(CryptoProvider::get_default().is_none());
+}
@@ -0,0 +1,60 @@
+//! The same client as [`super::Client`], for code that is not async. It can't be used from
+//! within an async runtime, as it runs one of its own.
+
+use serde::de::DeserializeOwned;
+
+use super::{batch_query, decode, tls_config, GraphOptions, Result, Routes};
+use crate::response::{GraphData, GraphList};
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::blocking::Client,
+    routes: Routes,
+}
+
+impl Client {
+    /// A client for the server at `base`, e.g. `http://localhost:8999`.
+    pub fn new(base: &str) -> Result<Self> {
+        let routes = Routes::new(base)?;
+        Ok(Client {
+            http: reqwest::blocking::Client::builder()
+                .tls_backend_preconfigured(tls_config()?)
+                .build()?,
+            routes,
+        })
+    }
+
+    /// Uses `http` to make requests, for its timeouts, proxies and so on. As with
+    /// [`super::Client::with_http_client`], it needs a crypto provider to have been built.
+    pub fn with_http_client(http: reqwest::blocking::Client, base: &str) -> Result<Self> {
+        Ok(Client {
+            http,
+            routes: Routes::new(base)?,
+        })
+    }
+
+    /// Every graph the server has, without their points.
+    pub fn list_graphs(&self) -> Result<GraphList> {
+        self.get(self.http.get(self.routes.list_graphs()))
+    }
+
+    pub fn graph(&self, name: &str, options: &GraphOptions) -> Result<GraphData> {
+        let request = self.http.get(self.routes.graph(name)).query(options);
+        self.get(request)
+    }
+
+    /// Several graphs in one request, in the order they were asked for.
+    pub fn graphs(&self, names: &[&str]) -> Result<Vec<GraphData>> {
+        let request = self
+            .http
+            .get(self.routes.batch())
+            .query(&batch_query(names));
+        self.get(request)
+    }
+
+    fn get<T: DeserializeOwned>(&self, request: reqwest::blocking::RequestBuilder) -> Result<T> {
+        let response = request.send()?;
+        let status = response.status();
+        decode(status, &response.bytes()?)
+    }
+}
@@ -0,0 +1,83 @@
+//! Postcard is not self-describing, so a client built against other versions of the types in
+//! [`crate::response`] would misread them without noticing. Every postcard payload starts with
+//! the version it was encoded with, so that a mismatch is caught before the rest is read.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever a change to the response types changes how they encode.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Encoded with another version of the response types.
+    Version {
+        found: u32,
+    },
+    Postcard(postcard::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Version { found } => {
+                write!(
+                    f,
+                    "encoded with version {found}, expected version {VERSION}"
+                )
+            }
+            Error::Postcard(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<postcard::Error> for Error {
+    fn from(e: postcard::Error) -> Self {
+        Error::Postcard(e)
+    }
+}
+
+/// Encodes `value` as postcard after the version header.
+pub fn seal<T: Serialize + ?Sized>(value: &T) -> postcard::Result<Vec<u8>> {
+    postcard::to_allocvec(&(VERSION, value))
+}
+
+/// The postcard encoded value in `bytes`, after checking its version.
+pub fn body(bytes: &[u8]) -> Result<&[u8], Error> {
+    let (found, rest) = postcard::take_from_bytes::<u32>(bytes)?;
+    if found != VERSION {
+        return Err(Error::Version { found });
+    }
+    Ok(rest)
+}
+
+/// Decodes a value encoded by [`seal`].
+pub fn open<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Error> {
+    Ok(postcard::from_bytes(body(bytes)?)?)
+}
+
+#[test]
+fn test_round_trip() {
+    let sealed = seal(&("sea level", 3.5f32)).unwrap();
+    assert_eq!(
+        body(&sealed).unwrap(),
+        postcard::to_allocvec(&("sea level", 3.5f32)).unwrap()
+    );
+    assert_eq!(
+        open::<(String, f32)>(&sealed).unwrap(),
+        ("sea level".to_string(), 3.5)
+    );
+}
+
+#[test]
+fn test_version_mismatch() {
+    let newer = postcard::to_allocvec(&(VERSION + 1, "sea level")).unwrap();
+    assert!(matches!(
+        open::<String>(&newer),
+        Err(Error::Version { found }) if found == VERSION + 1
+    ));
+    assert!(matches!(open::<String>(&[]), Err(Error::Postcard(_))));
+}
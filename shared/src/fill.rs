@@ -0,0 +1,14 @@
+//! How the gaps in a graph can be filled in, shared by the server's query and the clients' options.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "openapi", schema(as = FillMethod))]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    Linear,
+    Nearest,
+    /// Natural cubic spline through every observation.
+    Spline,
+}
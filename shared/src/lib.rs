@@ -1 +1,6 @@
+#[cfg(feature = "client")]
+pub mod client;
+pub mod envelope;
+pub mod fill;
 pub mod response;
+pub mod series;
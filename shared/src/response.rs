@@ -3,12 +3,16 @@ use std::collections::HashMap;
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
+use crate::series::TimeSeries;
+
 #[derive(Serialize, Clone, Deserialize, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct GraphList {
     pub graphs: Vec<GraphSummary>,
 }
 
 #[derive(Serialize, Clone, Deserialize, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct GraphIndex {
     pub graphs: HashMap<String, GraphSummary>,
 }
@@ -16,13 +20,18 @@ pub struct GraphIndex {
 // #[derive(Serialize, Clone, Deserialize, Debug, Eq, PartialEq)]
 #[derive(Clone, Debug)]
 pub struct Graph {
-    pub name: &'static str,
-    pub description: &'static str,
+    pub name: String,
+    pub description: String,
+    /// What the values are measured in, e.g. `mm`.
+    pub units: String,
+    /// Where the data comes from, for crediting it alongside the graph.
+    pub citation: String,
     pub color: (u8, u8, u8),
-    pub points: Points,
+    pub points: TimeSeries,
 }
 
 #[derive(Serialize, Clone, Deserialize, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct GraphSummary {
     pub name: String,
     pub uri: String,
@@ -30,46 +39,168 @@ pub struct GraphSummary {
     pub color: (u8, u8, u8),
 }
 
-pub type Points = Vec<(NaiveDate, f32)>;
-
 #[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct GraphData {
     pub name: String,
     pub color: (u8, u8, u8),
-    pub points: Points,
+    pub points: TimeSeries,
+    /// Indices of points that were interpolated rather than observed.
+    pub filled: Vec<u32>,
 }
 
 impl GraphData {
     pub fn max_x(&self) -> NaiveDate {
-        // self.points
-        //     .iter()
-        //     .map(|(x, _)| x)
-        //     .fold(NaiveDate::MIN, |a, b| a.max(*b))
-        // XXX: If we assume this and all graphs are left-right time series, this should always be
-        // true?
-        self.points.last().map(|point| point.0).unwrap_or_default()
+        self.points.max_x()
     }
 
     pub fn min_x(&self) -> NaiveDate {
-        // self.points
-        //     .iter()
-        //     .map(|(x, _)| x)
-        //     .fold(NaiveDate::MAX, |a, b| a.min(*b))
-        // XXX: See above
-        self.points.first().map(|point| point.0).unwrap_or_default()
+        self.points.min_x()
     }
 
     pub fn max_y(&self) -> f32 {
-        self.points
-            .iter()
-            .map(|(_, y)| y)
-            .fold(f32::MIN, |a, b| a.max(*b))
+        self.points.max_y()
     }
 
     pub fn min_y(&self) -> f32 {
-        self.points
-            .iter()
-            .map(|(_, y)| y)
-            .fold(f32::MAX, |a, b| a.min(*b))
+        self.points.min_y()
     }
 }
+
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChangePoints {
+    pub name: String,
+    pub breakpoints: Vec<NaiveDate>,
+    pub segments: Vec<Segment>,
+}
+
+/// A stretch of a graph between two change points, with its mean and least-squares slope (per
+/// year).
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Segment {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub mean: f32,
+    pub slope: f32,
+}
+
+/// A graph split into a long-term trend, a repeating seasonal cycle and whatever is left over,
+/// which add back up to the original points.
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Decomposition {
+    pub name: String,
+    pub period: u32,
+    pub trend: GraphData,
+    pub seasonal: GraphData,
+    pub residual: GraphData,
+}
+
+/// Spectral power of a graph against period, in years.
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Periodogram {
+    pub name: String,
+    pub color: (u8, u8, u8),
+    pub points: Vec<(f32, f32)>,
+}
+
+/// How closely two graphs agree over the period they overlap.
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Comparison {
+    pub a: String,
+    pub b: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub samples: u32,
+    pub pearson: f32,
+    pub spearman: f32,
+    pub rmse: f32,
+    /// Mean of `b - a`.
+    pub bias: f32,
+    pub cross_correlation: Vec<LagCorrelation>,
+}
+
+/// Correlation of `a` against `b` shifted later by `lag` samples, roughly `days` apart.
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LagCorrelation {
+    pub lag: i32,
+    pub days: i32,
+    pub correlation: f32,
+}
+
+/// A projection of a graph past its last observation. Every point in it is modeled, none are
+/// observed.
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Forecast {
+    pub name: String,
+    pub color: (u8, u8, u8),
+    pub method: String,
+    /// The date of the last observation the projection is based on.
+    pub observed_until: NaiveDate,
+    /// Confidence level of the `lower` and `upper` bounds, e.g. `0.95`.
+    pub level: f32,
+    pub points: Vec<ForecastPoint>,
+}
+
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ForecastPoint {
+    pub date: NaiveDate,
+    pub value: f32,
+    pub lower: f32,
+    pub upper: f32,
+}
+
+#[derive(Serialize, Clone, Deserialize, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QualityReport {
+    pub name: String,
+    pub issues: Vec<Issue>,
+}
+
+/// Something suspicious about the point at `index`.
+#[derive(Serialize, Clone, Deserialize, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub index: u32,
+    pub date: NaiveDate,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Copy, Deserialize, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum IssueKind {
+    /// Earlier than the point before it.
+    Unsorted,
+    /// Has the same date as another point.
+    Duplicate,
+    /// Further from the point before it than the typical spacing, but not by whole steps.
+    IrregularInterval,
+    /// Follows one or more missing points.
+    Gap,
+    Outlier,
+}
+
+/// Several graphs resampled onto a shared grid of dates, one column per graph.
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<TableRow>,
+}
+
+/// The start of a grid cell and the average of each graph within it, `None` where a graph has
+/// no points in the cell.
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TableRow {
+    pub date: NaiveDate,
+    pub values: Vec<Option<f32>>,
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+use std::ops::{Bound, Deref, RangeBounds};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize, Serializer};
+
+pub type Point = (NaiveDate, f32);
+
+/// Points ordered strictly left to right by date, so there is at most one value per date.
+///
+/// The extents are worked out once when the series is built, and it serializes as a plain list
+/// of points. Deserializing rejects a list that is out of order rather than quietly fixing it,
+/// as that means whoever encoded it did not go through here.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "Vec<Point>")]
+pub struct TimeSeries {
+    points: Vec<Point>,
+    min_y: f32,
+    max_y: f32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TimeSeriesError {
+    /// The point at `index` is earlier than the one before it.
+    Unsorted { index: usize },
+    /// The point at `index` has the same date as the one before it.
+    Duplicate { index: usize },
+}
+
+impl fmt::Display for TimeSeriesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeSeriesError::Unsorted { index } => {
+                write!(f, "point {index} is earlier than the point before it")
+            }
+            TimeSeriesError::Duplicate { index } => {
+                write!(f, "point {index} has the same date as the point before it")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimeSeriesError {}
+
+impl TimeSeries {
+    /// Sorts `points` by date, keeping only the first of any points that share a date.
+    pub fn new(mut points: Vec<Point>) -> Self {
+        points.sort_by_key(|(date, _)| *date);
+        points.dedup_by_key(|(date, _)| *date);
+        Self::new_unchecked(points)
+    }
+
+    fn new_unchecked(points: Vec<Point>) -> Self {
+        let (min_y, max_y) = points
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), (_, y)| {
+                (min.min(*y), max.max(*y))
+            });
+        TimeSeries {
+            points,
+            min_y,
+            max_y,
+        }
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    pub fn into_points(self) -> Vec<Point> {
+        self.points
+    }
+
+    /// The points whose dates fall within `range`, found by binary search.
+    pub fn range(&self, range: impl RangeBounds<NaiveDate>) -> &[Point] {
+        let start = match range.start_bound() {
+            Bound::Included(from) => self.points.partition_point(|(date, _)| date < from),
+            Bound::Excluded(from) => self.points.partition_point(|(date, _)| date <= from),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(to) => self.points.partition_point(|(date, _)| date <= to),
+            Bound::Excluded(to) => self.points.partition_point(|(date, _)| date < to),
+            Bound::Unbounded => self.points.len(),
+        };
+        &self.points[start..end.max(start)]
+    }
+
+    pub fn min_x(&self) -> NaiveDate {
+        self.points.first().map(|point| point.0).unwrap_or_default()
+    }
+
+    pub fn max_x(&self) -> NaiveDate {
+        self.points.last().map(|point| point.0).unwrap_or_default()
+    }
+
+    pub fn min_y(&self) -> f32 {
+        self.min_y
+    }
+
+    pub fn max_y(&self) -> f32 {
+        self.max_y
+    }
+}
+
+impl Default for TimeSeries {
+    fn default() -> Self {
+        Self::new_unchecked(vec![])
+    }
+}
+
+impl Deref for TimeSeries {
+    type Target = [Point];
+
+    fn deref(&self) -> &[Point] {
+        &self.points
+    }
+}
+
+impl TryFrom<Vec<Point>> for TimeSeries {
+    type Error = TimeSeriesError;
+
+    /// Accepts `points` only if they are already strictly ordered by date.
+    fn try_from(points: Vec<Point>) -> Result<Self, Self::Error> {
+        for (index, pair) in points.windows(2).enumerate() {
+            if pair[1].0 < pair[0].0 {
+                return Err(TimeSeriesError::Unsorted { index: index + 1 });
+            }
+            if pair[1].0 == pair[0].0 {
+                return Err(TimeSeriesError::Duplicate { index: index + 1 });
+            }
+        }
+        Ok(Self::new_unchecked(points))
+    }
+}
+
+impl FromIterator<Point> for TimeSeries {
+    fn from_iter<I: IntoIterator<Item = Point>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+impl Serialize for TimeSeries {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.points.serialize(serializer)
+    }
+}
+
+/// Described as it serializes, a list of `[date, value]` pairs.
+#[cfg(feature = "openapi")]
+impl utoipa::PartialSchema for TimeSeries {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::schema::{ArrayBuilder, ArrayItems, KnownFormat, ObjectBuilder};
+        use utoipa::openapi::{SchemaFormat, Type};
+
+        let point = ArrayBuilder::new()
+            .prefix_items([
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .format(Some(SchemaFormat::KnownFormat(KnownFormat::Date))),
+                ObjectBuilder::new()
+                    .schema_type(Type::Number)
+                    .format(Some(SchemaFormat::KnownFormat(KnownFormat::Float))),
+            ])
+            .items(ArrayItems::False)
+            .min_items(Some(2));
+        ArrayBuilder::new()
+            .items(point)
+            .description(Some("Points strictly ordered by date"))
+            .into()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::ToSchema for TimeSeries {}
+
+#[cfg(test)]
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2000, 1, day).unwrap()
+}
+
+#[test]
+fn test_new_sorts_and_deduplicates() {
+    let series = TimeSeries::new(vec![(date(3), 3.0), (date(1), 1.0), (date(3), 4.0)]);
+    assert_eq!(series.points(), &[(date(1), 1.0), (date(3), 3.0)]);
+    assert_eq!((series.min_x(), series.max_x()), (date(1), date(3)));
+    assert_eq!((series.min_y(), series.max_y()), (1.0, 3.0));
+}
+
+#[test]
+fn test_try_from_rejects() {
+    assert_eq!(
+        TimeSeries::try_from(vec![(date(2), 0.0), (date(1), 0.0)]),
+        Err(TimeSeriesError::Unsorted { index: 1 })
+    );
+    assert_eq!(
+        TimeSeries::try_from(vec![(date(1), 0.0), (date(2), 0.0), (date(2), 0.0)]),
+        Err(TimeSeriesError::Duplicate { index: 2 })
+    );
+}
+
+#[test]
+fn test_range() {
+    let series: TimeSeries = (1..=9).map(|day| (date(day), day as f32)).collect();
+    assert_eq!(series.range(date(3)..date(5)).len(), 2);
+    assert_eq!(series.range(date(3)..=date(5)).len(), 3);
+    assert_eq!(series.range(date(8)..).len(), 2);
+    assert_eq!(series.range(..date(1)).len(), 0);
+    assert_eq!(series.range(date(6)..date(2)).len(), 0);
+}
+
+#[test]
+fn test_serde_round_trip() {
+    let series: TimeSeries = (1..=3).map(|day| (date(day), day as f32)).collect();
+    let bytes = postcard::to_allocvec(&series).unwrap();
+    assert_eq!(postcard::from_bytes::<TimeSeries>(&bytes).unwrap(), series);
+
+    let unsorted = postcard::to_allocvec(&vec![(date(2), 0.0f32), (date(1), 0.0f32)]).unwrap();
+    assert!(postcard::from_bytes::<TimeSeries>(&unsorted).is_err());
+}
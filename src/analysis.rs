@@ -0,0 +1,152 @@
+use chrono::{Datelike, NaiveDate};
+
+pub mod changepoint;
+pub mod compare;
+pub mod fill;
+pub mod forecast;
+pub mod quality;
+pub mod resample;
+pub mod spectral;
+pub mod stl;
+
+/// Converts a date into fractional years, which is the x axis all of the analysis routines work
+/// in.
+pub fn decimal_year(date: &NaiveDate) -> f64 {
+    let days_in_year = NaiveDate::from_ymd_opt(date.year(), 12, 31)
+        .map(|d| d.ordinal())
+        .unwrap_or(365);
+    date.year() as f64 + date.ordinal0() as f64 / days_in_year as f64
+}
+
+/// Ordinary least squares fit of `ys` against `xs`, returning `(intercept, slope)`.
+pub fn linear_fit(xs: &[f64], ys: &[f64]) -> (f64, f64) {
+    let n = xs.len() as f64;
+    if n == 0.0 {
+        return (0.0, 0.0);
+    }
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let (sxx, sxy) = xs.iter().zip(ys).fold((0.0, 0.0), |(sxx, sxy), (x, y)| {
+        (
+            sxx + (x - mean_x).powi(2),
+            sxy + (x - mean_x) * (y - mean_y),
+        )
+    });
+    let slope = if sxx > 0.0 { sxy / sxx } else { 0.0 };
+    (mean_y - slope * mean_x, slope)
+}
+
+pub fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// The typical number of days between consecutive points, or `None` if there are too few points
+/// to tell.
+pub fn sampling_interval(points: &[(NaiveDate, f32)]) -> Option<f64> {
+    let intervals: Vec<f64> = points
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0).num_days() as f64)
+        .collect();
+    (!intervals.is_empty()).then(|| median(&intervals))
+}
+
+/// The spacing of a regularly sampled series. Monthly and quarterly records stay on the same day
+/// of the month, which a fixed number of days would drift away from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Step {
+    Days(u64),
+    Months(u32),
+}
+
+impl Step {
+    pub fn detect(points: &[(NaiveDate, f32)]) -> Option<Step> {
+        let interval = sampling_interval(points)?;
+        if points.windows(2).all(|w| w[0].0.day() == w[1].0.day()) {
+            let months: Vec<f64> = points
+                .windows(2)
+                .map(|w| months_between(&w[0].0, &w[1].0) as f64)
+                .collect();
+            let months = median(&months).round();
+            if months >= 1.0 {
+                return Some(Step::Months(months as u32));
+            }
+        }
+        (interval >= 1.0).then(|| Step::Days(interval.round() as u64))
+    }
+
+    /// The date `n` steps after `date`.
+    pub fn advance(&self, date: &NaiveDate, n: u32) -> Option<NaiveDate> {
+        match self {
+            Step::Days(days) => date.checked_add_days(chrono::Days::new(days * n as u64)),
+            Step::Months(months) => date.checked_add_months(chrono::Months::new(months * n)),
+        }
+    }
+
+    /// How many steps after `from` that `to` falls, rounded to the nearest step.
+    pub fn count(&self, from: &NaiveDate, to: &NaiveDate) -> i64 {
+        match self {
+            Step::Days(days) => ((*to - *from).num_days() as f64 / *days as f64).round() as i64,
+            Step::Months(months) => {
+                (months_between(from, to) as f64 / *months as f64).round() as i64
+            }
+        }
+    }
+}
+
+fn months_between(from: &NaiveDate, to: &NaiveDate) -> i64 {
+    (to.year() as i64 * 12 + to.month0() as i64) - (from.year() as i64 * 12 + from.month0() as i64)
+}
+
+#[test]
+fn test_decimal_year() {
+    assert_eq!(
+        decimal_year(&NaiveDate::from_ymd_opt(2000, 1, 1).unwrap()),
+        2000.0
+    );
+    assert_eq!(
+        decimal_year(&NaiveDate::from_ymd_opt(2004, 7, 2).unwrap()),
+        2004.5
+    );
+}
+
+#[test]
+fn test_linear_fit() {
+    let (intercept, slope) = linear_fit(&[0.0, 1.0, 2.0, 3.0], &[1.0, 3.0, 5.0, 7.0]);
+    assert_eq!(intercept, 1.0);
+    assert_eq!(slope, 2.0);
+}
+
+#[test]
+fn test_step_detect() {
+    let quarterly: Vec<(NaiveDate, f32)> = (0..8)
+        .map(|i| {
+            (
+                NaiveDate::from_ymd_opt(1990, 1, 15).unwrap() + chrono::Months::new(3 * i),
+                0.0,
+            )
+        })
+        .collect();
+    let step = Step::detect(&quarterly).unwrap();
+    assert_eq!(step, Step::Months(3));
+    assert_eq!(step.count(&quarterly[0].0, &quarterly[7].0), 7);
+
+    let daily: Vec<(NaiveDate, f32)> = (0..8)
+        .map(|i| {
+            (
+                NaiveDate::from_ymd_opt(1990, 1, 30).unwrap() + chrono::Days::new(i),
+                0.0,
+            )
+        })
+        .collect();
+    assert_eq!(Step::detect(&daily), Some(Step::Days(1)));
+}
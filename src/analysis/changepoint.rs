@@ -0,0 +1,264 @@
+use chrono::NaiveDate;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use shared::response::Segment;
+
+use super::{decimal_year, linear_fit};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[schema(as = ChangePointMethod)]
+pub enum Method {
+    /// Pruned Exact Linear Time, finds the optimal segmentation for the given penalty.
+    #[default]
+    Pelt,
+    /// Binary segmentation, greedily splits at the best point until no split pays the penalty.
+    BinSeg,
+}
+
+/// What is assumed to stay constant within a segment.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[schema(as = ChangePointModel)]
+pub enum Model {
+    Mean,
+    /// A straight line, so a trending series is only split where its rate changes.
+    #[default]
+    Linear,
+}
+
+impl Model {
+    fn parameters(&self) -> f64 {
+        match self {
+            Model::Mean => 1.0,
+            Model::Linear => 2.0,
+        }
+    }
+}
+
+/// Prefix sums that make the residual sum of squares of any segment O(1).
+struct Sums {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    xx: Vec<f64>,
+    xy: Vec<f64>,
+    yy: Vec<f64>,
+}
+
+impl Sums {
+    fn new(xs: &[f64], ys: &[f64]) -> Self {
+        let mut sums = Sums {
+            x: vec![0.0],
+            y: vec![0.0],
+            xx: vec![0.0],
+            xy: vec![0.0],
+            yy: vec![0.0],
+        };
+        for (x, y) in xs.iter().zip(ys) {
+            sums.x.push(sums.x.last().unwrap() + x);
+            sums.y.push(sums.y.last().unwrap() + y);
+            sums.xx.push(sums.xx.last().unwrap() + x * x);
+            sums.xy.push(sums.xy.last().unwrap() + x * y);
+            sums.yy.push(sums.yy.last().unwrap() + y * y);
+        }
+        sums
+    }
+
+    /// Residual sum of squares of `model` fitted to the points in `start..end`.
+    fn cost(&self, model: Model, start: usize, end: usize) -> f64 {
+        let n = (end - start) as f64;
+        let x = self.x[end] - self.x[start];
+        let y = self.y[end] - self.y[start];
+        let syy = self.yy[end] - self.yy[start] - y * y / n;
+        let cost = match model {
+            Model::Mean => syy,
+            Model::Linear => {
+                let sxx = self.xx[end] - self.xx[start] - x * x / n;
+                let sxy = self.xy[end] - self.xy[start] - x * y / n;
+                if sxx > f64::EPSILON {
+                    syy - sxy * sxy / sxx
+                } else {
+                    syy
+                }
+            }
+        };
+        cost.max(0.0)
+    }
+}
+
+/// Finds the indices at which a new segment starts in `points`, which must be sorted by date.
+///
+/// When no penalty is given a BIC style one is used, scaled by the residual variance of a single
+/// segment. That overestimates the noise when there are real changes, which errs on the side of
+/// reporting fewer of them: sea level records are strongly autocorrelated and anything more
+/// eager splits every wiggle.
+pub fn detect(
+    points: &[(NaiveDate, f32)],
+    method: Method,
+    model: Model,
+    penalty: Option<f64>,
+    min_size: usize,
+) -> Vec<usize> {
+    let min_size = min_size.max(model.parameters() as usize + 1);
+    if points.len() < min_size.saturating_mul(2) {
+        return vec![];
+    }
+
+    let origin = decimal_year(&points[0].0);
+    let xs: Vec<f64> = points
+        .iter()
+        .map(|(date, _)| decimal_year(date) - origin)
+        .collect();
+    let ys: Vec<f64> = points.iter().map(|(_, y)| *y as f64).collect();
+    let sums = Sums::new(&xs, &ys);
+
+    let penalty = penalty.unwrap_or_else(|| {
+        let n = points.len() as f64;
+        let variance = sums.cost(model, 0, points.len()) / n;
+        model.parameters() * variance * n.ln()
+    });
+
+    match method {
+        Method::Pelt => pelt(&sums, model, penalty, min_size, points.len()),
+        Method::BinSeg => {
+            let mut breakpoints = vec![];
+            binary_segmentation(
+                &sums,
+                model,
+                penalty,
+                min_size,
+                0,
+                points.len(),
+                &mut breakpoints,
+            );
+            breakpoints.sort_unstable();
+            breakpoints
+        }
+    }
+}
+
+fn pelt(sums: &Sums, model: Model, penalty: f64, min_size: usize, n: usize) -> Vec<usize> {
+    let mut best = vec![f64::INFINITY; n + 1];
+    let mut previous = vec![0; n + 1];
+    best[0] = -penalty;
+
+    let mut candidates = vec![0];
+    for end in min_size..=n {
+        for &start in candidates.iter().filter(|&&start| end - start >= min_size) {
+            let total = best[start] + sums.cost(model, start, end) + penalty;
+            if total < best[end] {
+                best[end] = total;
+                previous[end] = start;
+            }
+        }
+
+        candidates.retain(|&start| {
+            end - start < min_size || best[start] + sums.cost(model, start, end) <= best[end]
+        });
+        if best[end].is_finite() {
+            candidates.push(end);
+        }
+    }
+
+    let mut breakpoints = vec![];
+    let mut end = n;
+    while end > 0 {
+        end = previous[end];
+        if end > 0 {
+            breakpoints.push(end);
+        }
+    }
+    breakpoints.reverse();
+    breakpoints
+}
+
+fn binary_segmentation(
+    sums: &Sums,
+    model: Model,
+    penalty: f64,
+    min_size: usize,
+    start: usize,
+    end: usize,
+    breakpoints: &mut Vec<usize>,
+) {
+    if end - start < 2 * min_size {
+        return;
+    }
+
+    let whole = sums.cost(model, start, end);
+    let best = (start + min_size..=end - min_size)
+        .map(|split| {
+            (
+                split,
+                sums.cost(model, start, split) + sums.cost(model, split, end),
+            )
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+
+    if let Some((split, cost)) = best {
+        if whole - cost > penalty {
+            breakpoints.push(split);
+            binary_segmentation(sums, model, penalty, min_size, start, split, breakpoints);
+            binary_segmentation(sums, model, penalty, min_size, split, end, breakpoints);
+        }
+    }
+}
+
+/// Describes the segments of `points` delimited by `breakpoints`.
+pub fn segments(points: &[(NaiveDate, f32)], breakpoints: &[usize]) -> Vec<Segment> {
+    let bounds = std::iter::once(0)
+        .chain(breakpoints.iter().copied())
+        .chain(std::iter::once(points.len()));
+    bounds
+        .clone()
+        .zip(bounds.skip(1))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| {
+            let segment = &points[start..end];
+            let xs: Vec<f64> = segment.iter().map(|(date, _)| decimal_year(date)).collect();
+            let ys: Vec<f64> = segment.iter().map(|(_, y)| *y as f64).collect();
+            let (_, slope) = linear_fit(&xs, &ys);
+            Segment {
+                start: segment[0].0,
+                end: segment[segment.len() - 1].0,
+                mean: (ys.iter().sum::<f64>() / ys.len() as f64) as f32,
+                slope: slope as f32,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn step_series() -> Vec<(NaiveDate, f32)> {
+    (0..60)
+        .map(|i| {
+            let date = NaiveDate::from_ymd_opt(1900 + i, 1, 15).unwrap();
+            let noise = (i as f32 * 1.7).sin() * 0.5;
+            let level = if i < 25 { 0.0 } else { 10.0 };
+            (date, level + noise)
+        })
+        .collect()
+}
+
+#[test]
+fn test_detect_step() {
+    let points = step_series();
+    for method in [Method::Pelt, Method::BinSeg] {
+        assert_eq!(detect(&points, method, Model::Mean, None, 2), vec![25]);
+        // Segments longer than the series find nothing, rather than overflowing
+        assert!(detect(&points, method, Model::Mean, None, usize::MAX).is_empty());
+    }
+}
+
+#[test]
+fn test_segments() {
+    let points = step_series();
+    let segments = segments(&points, &[25]);
+    assert_eq!(segments.len(), 2);
+    assert_eq!(
+        segments[1].start,
+        NaiveDate::from_ymd_opt(1925, 1, 15).unwrap()
+    );
+    assert!((segments[1].mean - 10.0).abs() < 0.1);
+}
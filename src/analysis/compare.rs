@@ -0,0 +1,145 @@
+use chrono::NaiveDate;
+
+/// Pairs every point of `a` that falls within the span of `b` with the value of `b` linearly
+/// interpolated at the same date. Both must be sorted by date.
+pub fn align(a: &[(NaiveDate, f32)], b: &[(NaiveDate, f32)]) -> Vec<(NaiveDate, f64, f64)> {
+    let mut aligned = vec![];
+    let mut j = 0;
+    for (date, value) in a {
+        while j + 1 < b.len() && b[j + 1].0 < *date {
+            j += 1;
+        }
+        if j + 1 >= b.len() || b[j].0 > *date {
+            if let Some((only, y)) = b.get(j) {
+                if only == date {
+                    aligned.push((*date, *value as f64, *y as f64));
+                }
+            }
+            continue;
+        }
+
+        let (d0, y0) = b[j];
+        let (d1, y1) = b[j + 1];
+        let span = (d1 - d0).num_days() as f64;
+        let offset = (*date - d0).num_days() as f64;
+        let y = if span > 0.0 {
+            y0 as f64 + (y1 as f64 - y0 as f64) * offset / span
+        } else {
+            y0 as f64
+        };
+        aligned.push((*date, *value as f64, y));
+    }
+    aligned
+}
+
+pub fn pearson(xs: &[f64], ys: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        sxy += (x - mean_x) * (y - mean_y);
+        sxx += (x - mean_x).powi(2);
+        syy += (y - mean_y).powi(2);
+    }
+    if sxx > 0.0 && syy > 0.0 {
+        sxy / (sxx * syy).sqrt()
+    } else {
+        f64::NAN
+    }
+}
+
+pub fn spearman(xs: &[f64], ys: &[f64]) -> f64 {
+    pearson(&ranks(xs), &ranks(ys))
+}
+
+/// Ranks of `values` starting at 1, with tied values sharing the average of their ranks.
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+/// Pearson correlation of `xs[i]` against `ys[i + lag]` for every lag in `-max_lag..=max_lag`,
+/// skipping lags that leave fewer than three overlapping samples. Lags longer than the series
+/// are never included, however large `max_lag` is.
+pub fn cross_correlation(xs: &[f64], ys: &[f64], max_lag: usize) -> Vec<(i32, f64)> {
+    let n = xs.len().min(ys.len());
+    let max_lag = max_lag.min(n.saturating_sub(1)) as isize;
+    let n = n as isize;
+    (-max_lag..=max_lag)
+        .filter_map(|lag| {
+            let start = 0.max(-lag);
+            let end = n.min(n - lag);
+            if end - start < 3 {
+                return None;
+            }
+            let x = &xs[start as usize..end as usize];
+            let y = &ys[(start + lag) as usize..(end + lag) as usize];
+            Some((lag as i32, pearson(x, y)))
+        })
+        .collect()
+}
+
+#[test]
+fn test_align_interpolates() {
+    let date = |month| NaiveDate::from_ymd_opt(2000, month, 1).unwrap();
+    let a = [
+        (date(1), 1.0),
+        (date(2), 2.0),
+        (date(3), 3.0),
+        (date(6), 4.0),
+    ];
+    let b = [(date(2), 10.0), (date(4), 30.0)];
+    let aligned = align(&a, &b);
+    assert_eq!(aligned.len(), 2);
+    assert_eq!(aligned[0], (date(2), 2.0, 10.0));
+    assert_eq!(aligned[1].0, date(3));
+    assert!((aligned[1].2 - 19.667).abs() < 0.01);
+}
+
+#[test]
+fn test_cross_correlation_clamps_lag() {
+    let xs = [1.0, 2.0, 3.0, 4.0, 5.0];
+    let ys = [2.0, 1.0, 4.0, 3.0, 5.0];
+    let lags: Vec<i32> = cross_correlation(&xs, &ys, usize::MAX)
+        .iter()
+        .map(|(lag, _)| *lag)
+        .collect();
+    assert_eq!(lags, [-2, -1, 0, 1, 2]);
+    assert!(cross_correlation(&[], &[], usize::MAX).is_empty());
+}
+
+#[test]
+fn test_correlations() {
+    let xs = [1.0, 2.0, 3.0, 4.0, 5.0];
+    let ys = [1.0, 4.0, 9.0, 16.0, 25.0];
+    assert!((pearson(&xs, &ys) - 0.981).abs() < 0.001);
+    assert_eq!(spearman(&xs, &ys), 1.0);
+    assert_eq!(ranks(&[3.0, 1.0, 3.0]), vec![2.5, 1.0, 2.5]);
+}
+
+#[test]
+fn test_cross_correlation_lag() {
+    let xs: Vec<f64> = (0..50).map(|i| (i as f64 * 0.5).sin()).collect();
+    let ys: Vec<f64> = (0..50).map(|i| ((i as f64 - 3.0) * 0.5).sin()).collect();
+    let (lag, _) = cross_correlation(&xs, &ys, 5)
+        .into_iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+    assert_eq!(lag, 3);
+}
@@ -0,0 +1,157 @@
+use chrono::NaiveDate;
+pub use shared::fill::Method;
+
+use super::Step;
+
+/// Fills in the missing steps of internal gaps in `points` (sorted by date) that are at most
+/// `max_gap` steps long. Returns the filled series along with the indices of the points that
+/// were made up.
+pub fn fill_gaps(
+    points: &[(NaiveDate, f32)],
+    method: Method,
+    max_gap: usize,
+) -> (Vec<(NaiveDate, f32)>, Vec<u32>) {
+    let Some(step) = Step::detect(points) else {
+        return (points.to_vec(), vec![]);
+    };
+    let spline = (method == Method::Spline).then(|| Spline::new(points));
+
+    let mut filled = Vec::with_capacity(points.len());
+    let mut indices = vec![];
+    for pair in points.windows(2) {
+        let ((start, y0), (end, y1)) = (pair[0], pair[1]);
+        filled.push((start, y0));
+
+        let missing = step.count(&start, &end) - 1;
+        if missing < 1 || missing as usize > max_gap {
+            continue;
+        }
+        for n in 1..=missing as u32 {
+            let Some(date) = step.advance(&start, n).filter(|date| *date < end) else {
+                break;
+            };
+            let offset = (date - start).num_days() as f32;
+            let span = (end - start).num_days() as f32;
+            let value = match &spline {
+                Some(spline) => spline.at(date),
+                None if method == Method::Nearest => {
+                    if offset <= span - offset {
+                        y0
+                    } else {
+                        y1
+                    }
+                }
+                None => y0 + (y1 - y0) * offset / span,
+            };
+            indices.push(filled.len() as u32);
+            filled.push((date, value));
+        }
+    }
+    filled.extend(points.last());
+
+    (filled, indices)
+}
+
+/// Natural cubic spline, with x measured in days since the first point.
+struct Spline {
+    origin: NaiveDate,
+    xs: Vec<f64>,
+    ys: Vec<f64>,
+    /// Second derivative at each point.
+    curvature: Vec<f64>,
+}
+
+impl Spline {
+    fn new(points: &[(NaiveDate, f32)]) -> Self {
+        let origin = points[0].0;
+        let xs: Vec<f64> = points
+            .iter()
+            .map(|(date, _)| (*date - origin).num_days() as f64)
+            .collect();
+        let ys: Vec<f64> = points.iter().map(|(_, y)| *y as f64).collect();
+        let n = xs.len();
+
+        // Solve the tridiagonal system for the curvature, which is zero at either end
+        let mut curvature = vec![0.0; n];
+        let mut diagonal = vec![0.0; n];
+        let mut rhs = vec![0.0; n];
+        for i in 1..n.saturating_sub(1) {
+            let (h0, h1) = (xs[i] - xs[i - 1], xs[i + 1] - xs[i]);
+            let slope = (ys[i + 1] - ys[i]) / h1 - (ys[i] - ys[i - 1]) / h0;
+            diagonal[i] = 2.0 * (h0 + h1);
+            rhs[i] = 6.0 * slope;
+            if i > 1 {
+                let factor = h0 / diagonal[i - 1];
+                diagonal[i] -= factor * h0;
+                rhs[i] -= factor * rhs[i - 1];
+            }
+        }
+        for i in (1..n.saturating_sub(1)).rev() {
+            let h1 = xs[i + 1] - xs[i];
+            curvature[i] = (rhs[i] - h1 * curvature[i + 1]) / diagonal[i];
+        }
+
+        Spline {
+            origin,
+            xs,
+            ys,
+            curvature,
+        }
+    }
+
+    fn at(&self, date: NaiveDate) -> f32 {
+        let x = (date - self.origin).num_days() as f64;
+        let i = self
+            .xs
+            .partition_point(|xi| *xi <= x)
+            .clamp(1, self.xs.len() - 1);
+        let (x0, x1) = (self.xs[i - 1], self.xs[i]);
+        let h = x1 - x0;
+        let (a, b) = ((x1 - x) / h, (x - x0) / h);
+        let value = a * self.ys[i - 1]
+            + b * self.ys[i]
+            + ((a.powi(3) - a) * self.curvature[i - 1] + (b.powi(3) - b) * self.curvature[i])
+                * h
+                * h
+                / 6.0;
+        value as f32
+    }
+}
+
+#[cfg(test)]
+fn gappy_series() -> Vec<(NaiveDate, f32)> {
+    [1, 2, 5, 6, 7, 12]
+        .iter()
+        .map(|&month| {
+            let date = NaiveDate::from_ymd_opt(2000, month, 15).unwrap();
+            (date, month as f32)
+        })
+        .collect()
+}
+
+#[test]
+fn test_fill_linear() {
+    let (filled, indices) = fill_gaps(&gappy_series(), Method::Linear, 2);
+    // The four month gap at the end is too long to fill
+    assert_eq!(filled.len(), 8);
+    assert_eq!(indices, vec![2, 3]);
+    assert_eq!(filled[2].0, NaiveDate::from_ymd_opt(2000, 3, 15).unwrap());
+    assert!((filled[2].1 - 3.0).abs() < 0.05);
+}
+
+#[test]
+fn test_fill_nearest() {
+    let (filled, _) = fill_gaps(&gappy_series(), Method::Nearest, 2);
+    assert_eq!(filled[2].1, 2.0);
+    assert_eq!(filled[3].1, 5.0);
+}
+
+#[test]
+fn test_fill_spline() {
+    // Points on a straight line have no curvature, so the spline follows the line
+    let (filled, indices) = fill_gaps(&gappy_series(), Method::Spline, 4);
+    assert_eq!(indices, vec![2, 3, 7, 8, 9, 10]);
+    for (date, value) in filled {
+        assert!((value - date.format("%m").to_string().parse::<f32>().unwrap()).abs() < 0.1);
+    }
+}
@@ -0,0 +1,314 @@
+//! Projections of a graph beyond its last observation, each with a prediction interval.
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use shared::response::ForecastPoint;
+
+use super::{decimal_year, Step};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[schema(as = ForecastMethod)]
+pub enum Method {
+    #[default]
+    Linear,
+    Quadratic,
+    /// Holt's linear exponential smoothing, which weights recent observations more heavily.
+    Holt,
+}
+
+/// Projects `points` (sorted by date) forward for `years`, with intervals at the given confidence
+/// `level`. Returns `None` when there are too few points to fit `method`.
+pub fn forecast(
+    points: &[(NaiveDate, f32)],
+    method: Method,
+    years: f64,
+    level: f64,
+) -> Option<Vec<ForecastPoint>> {
+    let step = Step::detect(points)?;
+    let last = points.last()?.0;
+    let end = decimal_year(&last) + years;
+    let dates: Vec<NaiveDate> = (1..)
+        .map_while(|n| step.advance(&last, n))
+        .take_while(|date| decimal_year(date) <= end)
+        .collect();
+
+    let z = normal_quantile(0.5 + level / 2.0);
+    let xs: Vec<f64> = points.iter().map(|(date, _)| decimal_year(date)).collect();
+    let ys: Vec<f64> = points.iter().map(|(_, y)| *y as f64).collect();
+
+    let predictions: Vec<(f64, f64)> = match method {
+        Method::Linear | Method::Quadratic => {
+            let degree = if method == Method::Linear { 1 } else { 2 };
+            let fit = PolyFit::new(&xs, &ys, degree)?;
+            dates
+                .iter()
+                .map(|date| fit.predict(decimal_year(date)))
+                .collect()
+        }
+        Method::Holt => {
+            let holt = Holt::fit(&ys)?;
+            (1..=dates.len()).map(|h| holt.predict(h)).collect()
+        }
+    };
+
+    Some(
+        dates
+            .into_iter()
+            .zip(predictions)
+            .map(|(date, (value, variance))| {
+                let margin = z * variance.sqrt();
+                ForecastPoint {
+                    date,
+                    value: value as f32,
+                    lower: (value - margin) as f32,
+                    upper: (value + margin) as f32,
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Least squares polynomial, keeping what is needed to work out the prediction variance.
+struct PolyFit {
+    origin: f64,
+    coefficients: Vec<f64>,
+    /// `(XᵀX)⁻¹` of the design matrix.
+    inverse: Vec<Vec<f64>>,
+    variance: f64,
+}
+
+impl PolyFit {
+    fn new(xs: &[f64], ys: &[f64], degree: usize) -> Option<Self> {
+        let terms = degree + 1;
+        if xs.len() <= terms {
+            return None;
+        }
+        // Centre x so that the powers stay well conditioned
+        let origin = xs.iter().sum::<f64>() / xs.len() as f64;
+        let row =
+            |x: f64| -> Vec<f64> { (0..terms).map(|p| (x - origin).powi(p as i32)).collect() };
+
+        let mut xtx = vec![vec![0.0; terms]; terms];
+        let mut xty = vec![0.0; terms];
+        for (x, y) in xs.iter().zip(ys) {
+            let r = row(*x);
+            for i in 0..terms {
+                xty[i] += r[i] * y;
+                for j in 0..terms {
+                    xtx[i][j] += r[i] * r[j];
+                }
+            }
+        }
+        let inverse = invert(xtx)?;
+        let coefficients: Vec<f64> = inverse
+            .iter()
+            .map(|row| row.iter().zip(&xty).map(|(a, b)| a * b).sum())
+            .collect();
+
+        let sse: f64 = xs
+            .iter()
+            .zip(ys)
+            .map(|(x, y)| {
+                let fitted: f64 = row(*x).iter().zip(&coefficients).map(|(a, b)| a * b).sum();
+                (y - fitted).powi(2)
+            })
+            .sum();
+
+        Some(PolyFit {
+            origin,
+            coefficients,
+            inverse,
+            variance: sse / (xs.len() - terms) as f64,
+        })
+    }
+
+    /// The fitted value at `x` and the variance of a new observation there.
+    fn predict(&self, x: f64) -> (f64, f64) {
+        let r: Vec<f64> = (0..self.coefficients.len())
+            .map(|p| (x - self.origin).powi(p as i32))
+            .collect();
+        let value = r.iter().zip(&self.coefficients).map(|(a, b)| a * b).sum();
+        let leverage: f64 = self
+            .inverse
+            .iter()
+            .zip(&r)
+            .map(|(row, ri)| ri * row.iter().zip(&r).map(|(a, b)| a * b).sum::<f64>())
+            .sum();
+        (value, self.variance * (1.0 + leverage))
+    }
+}
+
+/// Gauss-Jordan inversion with partial pivoting, `None` if `matrix` is singular.
+fn invert(mut matrix: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut inverse: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))?;
+        if matrix[pivot][column].abs() < f64::EPSILON {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        inverse.swap(column, pivot);
+
+        let scale = matrix[column][column];
+        for j in 0..n {
+            matrix[column][j] /= scale;
+            inverse[column][j] /= scale;
+        }
+        for i in (0..n).filter(|&i| i != column) {
+            let factor = matrix[i][column];
+            for j in 0..n {
+                matrix[i][j] -= factor * matrix[column][j];
+                inverse[i][j] -= factor * inverse[column][j];
+            }
+        }
+    }
+    Some(inverse)
+}
+
+/// Holt's linear trend method, with the smoothing parameters chosen to minimise the one step
+/// ahead errors.
+struct Holt {
+    alpha: f64,
+    beta: f64,
+    level: f64,
+    trend: f64,
+    variance: f64,
+}
+
+impl Holt {
+    fn fit(ys: &[f64]) -> Option<Self> {
+        if ys.len() < 3 {
+            return None;
+        }
+        let grid = || (1..20).map(|i| i as f64 * 0.05);
+        grid()
+            .flat_map(|alpha| grid().map(move |beta| Holt::run(ys, alpha, beta)))
+            .min_by(|a, b| a.variance.total_cmp(&b.variance))
+    }
+
+    fn run(ys: &[f64], alpha: f64, beta: f64) -> Self {
+        let mut level = ys[0];
+        let mut trend = ys[1] - ys[0];
+        let mut sse = 0.0;
+        for y in &ys[1..] {
+            let error = y - (level + trend);
+            sse += error * error;
+            let previous = level;
+            level = alpha * y + (1.0 - alpha) * (level + trend);
+            trend = beta * (level - previous) + (1.0 - beta) * trend;
+        }
+        Holt {
+            alpha,
+            beta,
+            level,
+            trend,
+            variance: sse / (ys.len() - 1) as f64,
+        }
+    }
+
+    /// The forecast `h` steps ahead and its variance, see Hyndman & Athanasopoulos, Forecasting:
+    /// Principles and Practice, table 8.8.
+    fn predict(&self, h: usize) -> (f64, f64) {
+        let spread: f64 = (1..h)
+            .map(|j| (self.alpha * (1.0 + j as f64 * self.beta)).powi(2))
+            .sum();
+        (
+            self.level + h as f64 * self.trend,
+            self.variance * (1.0 + spread),
+        )
+    }
+}
+
+/// Inverse of the standard normal CDF, using Acklam's rational approximation.
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+
+    let p = p.clamp(1e-12, 1.0 - 1e-12);
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < 0.02425 {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - 0.02425 {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+#[cfg(test)]
+fn trend_series() -> Vec<(NaiveDate, f32)> {
+    (0..40)
+        .map(|i| {
+            let date = NaiveDate::from_ymd_opt(1980 + i, 1, 1).unwrap();
+            let noise = (i as f32 * 2.3).sin();
+            (date, 3.0 * i as f32 + noise)
+        })
+        .collect()
+}
+
+#[test]
+fn test_normal_quantile() {
+    assert!((normal_quantile(0.975) - 1.959964).abs() < 1e-6);
+    assert!((normal_quantile(0.5)).abs() < 1e-9);
+    assert!((normal_quantile(0.01) + 2.326348).abs() < 1e-6);
+}
+
+#[test]
+fn test_forecast_continues_trend() {
+    let points = trend_series();
+    for method in [Method::Linear, Method::Quadratic, Method::Holt] {
+        let forecast = forecast(&points, method, 10.0, 0.95).unwrap();
+        assert_eq!(forecast.len(), 10);
+        assert_eq!(
+            forecast[0].date,
+            NaiveDate::from_ymd_opt(2020, 1, 1).unwrap()
+        );
+
+        let last = forecast.last().unwrap();
+        assert!((last.value - 147.0).abs() < 5.0, "{method:?} {last:?}");
+        assert!(last.lower < last.value && last.value < last.upper);
+        // Uncertainty grows the further out the projection goes
+        assert!(last.upper - last.lower > forecast[0].upper - forecast[0].lower);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+
+use shared::response::{Issue, IssueKind};
+
+use super::{median, Step};
+
+/// Points either side of each point used to work out what its value would be expected to be.
+const OUTLIER_WINDOW: usize = 6;
+
+/// Modified z-score (Iglewicz & Hoaglin) above which a point is reported as an outlier. The usual
+/// 3.5 flags a dozen points in each sea level record, whose residuals are heavier tailed than
+/// normal.
+const OUTLIER_THRESHOLD: f64 = 5.0;
+
+/// Looks for problems in `points` as loaded, which unlike the rest of the analysis routines does
+/// not assume that they are sorted by date.
+pub fn check(points: &[(NaiveDate, f32)]) -> Vec<Issue> {
+    let mut issues = vec![];
+    let issue = |kind, index: usize, message| Issue {
+        kind,
+        index: index as u32,
+        date: points[index].0,
+        message,
+    };
+
+    let mut seen = HashMap::new();
+    for (index, (date, _)) in points.iter().enumerate() {
+        if let Some(first) = seen.insert(*date, index) {
+            issues.push(issue(
+                IssueKind::Duplicate,
+                index,
+                format!("{date} appears again, first at point {first}"),
+            ));
+        }
+    }
+
+    for (index, pair) in points.windows(2).enumerate().map(|(i, pair)| (i + 1, pair)) {
+        let (previous, date) = (pair[0].0, pair[1].0);
+        if date < previous {
+            issues.push(issue(
+                IssueKind::Unsorted,
+                index,
+                format!("{date} comes after {previous}"),
+            ));
+        }
+    }
+
+    // Spacing is checked in date order, so that a point out of place is not also reported as a
+    // gap either side of it
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by_key(|&i| points[i].0);
+    let sorted: Vec<(NaiveDate, f32)> = order.iter().map(|&i| points[i]).collect();
+    if let Some(step) = Step::detect(&sorted) {
+        for (&index, pair) in order[1..].iter().zip(sorted.windows(2)) {
+            let (previous, date) = (pair[0].0, pair[1].0);
+            if date == previous || step.advance(&previous, 1) == Some(date) {
+                continue;
+            }
+            let steps = step.count(&previous, &date);
+            if steps > 1 && step.advance(&previous, steps as u32) == Some(date) {
+                issues.push(issue(
+                    IssueKind::Gap,
+                    index,
+                    format!("{} missing between {previous} and {date}", steps - 1),
+                ));
+            } else if !within_tolerance(&step, &previous, &date) {
+                issues.push(issue(
+                    IssueKind::IrregularInterval,
+                    index,
+                    format!(
+                        "{} days after {previous}, expected every {step:?}",
+                        (date - previous).num_days()
+                    ),
+                ));
+            }
+        }
+    }
+
+    for index in outliers(points) {
+        issues.push(issue(
+            IssueKind::Outlier,
+            index,
+            format!("{} is out of line with its neighbours", points[index].1),
+        ));
+    }
+
+    issues.sort_by_key(|issue| issue.index);
+    issues
+}
+
+/// Daily and weekly records are allowed a little jitter, so long as it does not add up to a
+/// whole missing step.
+fn within_tolerance(step: &Step, previous: &NaiveDate, date: &NaiveDate) -> bool {
+    match step {
+        Step::Days(days) => {
+            let difference = (*date - *previous).num_days() - *days as i64;
+            difference.unsigned_abs() * 10 <= *days
+        }
+        Step::Months(_) => false,
+    }
+}
+
+/// Indices of points that stand out from the running median of their neighbours.
+fn outliers(points: &[(NaiveDate, f32)]) -> Vec<usize> {
+    let ys: Vec<f64> = points.iter().map(|(_, y)| *y as f64).collect();
+    let deviations: Vec<f64> = (0..ys.len())
+        .map(|i| {
+            let window =
+                &ys[i.saturating_sub(OUTLIER_WINDOW)..(i + OUTLIER_WINDOW + 1).min(ys.len())];
+            ys[i] - median(window)
+        })
+        .collect();
+
+    let center = median(&deviations);
+    let spread: Vec<f64> = deviations.iter().map(|d| (d - center).abs()).collect();
+    let mad = median(&spread);
+    if mad <= 0.0 {
+        return vec![];
+    }
+    spread
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| 0.6745 * *d / mad > OUTLIER_THRESHOLD)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+fn date(year: i32, month: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, 15).unwrap()
+}
+
+#[test]
+fn test_clean_series() {
+    let points: Vec<(NaiveDate, f32)> = (1..=12)
+        .map(|month| (date(2000, month), (month as f32).sin()))
+        .collect();
+    assert_eq!(check(&points), vec![]);
+}
+
+#[test]
+fn test_problems() {
+    let mut points: Vec<(NaiveDate, f32)> = (1..=12)
+        .map(|month| (date(2000, month), (month as f32 * 0.9).sin()))
+        .collect();
+    points[5].1 = 40.0;
+    points.remove(8);
+    points.swap(2, 3);
+    points.push((date(2000, 12), 0.0));
+
+    let kinds: Vec<(IssueKind, u32)> = check(&points)
+        .into_iter()
+        .map(|issue| (issue.kind, issue.index))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (IssueKind::Unsorted, 3),
+            (IssueKind::Outlier, 5),
+            (IssueKind::Gap, 8),
+            (IssueKind::Duplicate, 11),
+        ]
+    );
+}
+
+#[test]
+fn test_irregular_interval() {
+    let points = [
+        (date(2000, 1), 0.0),
+        (date(2000, 4), 0.0),
+        (date(2000, 7), 0.0),
+        (date(2000, 9), 0.0),
+        (date(2000, 12), 0.0),
+    ];
+    let issues = check(&points);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].kind, IssueKind::IrregularInterval);
+    assert_eq!(issues[0].index, 3);
+}
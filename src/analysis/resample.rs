@@ -0,0 +1,84 @@
+use chrono::{Datelike, Months, NaiveDate};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use shared::series::TimeSeries;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Grid {
+    #[default]
+    Monthly,
+    Quarterly,
+    Annual,
+}
+
+impl Grid {
+    fn months(&self) -> u32 {
+        match self {
+            Grid::Monthly => 1,
+            Grid::Quarterly => 3,
+            Grid::Annual => 12,
+        }
+    }
+
+    /// The first day of the cell that `date` falls in.
+    pub fn cell(&self, date: &NaiveDate) -> NaiveDate {
+        let month0 = date.month0() - date.month0() % self.months();
+        NaiveDate::from_ymd_opt(date.year(), month0 + 1, 1).unwrap()
+    }
+}
+
+/// Averages each series into the cells of `grid`, from the earliest to the latest cell covered
+/// by any of them. Cells a series has no points in are `None`.
+pub fn resample(series: &[&TimeSeries], grid: Grid) -> Vec<(NaiveDate, Vec<Option<f32>>)> {
+    let populated = series.iter().filter(|series| !series.is_empty());
+    let (Some(first), Some(last)) = (
+        populated
+            .clone()
+            .map(|series| grid.cell(&series.min_x()))
+            .min(),
+        populated.map(|series| grid.cell(&series.max_x())).max(),
+    ) else {
+        return vec![];
+    };
+
+    let mut rows = vec![];
+    let mut cell = first;
+    while cell <= last {
+        let Some(next) = cell.checked_add_months(Months::new(grid.months())) else {
+            break;
+        };
+        let values = series
+            .iter()
+            .map(|series| {
+                let points = series.range(cell..next);
+                (!points.is_empty()).then(|| {
+                    points.iter().map(|(_, y)| *y as f64).sum::<f64>() as f32 / points.len() as f32
+                })
+            })
+            .collect();
+        rows.push((cell, values));
+        cell = next;
+    }
+    rows
+}
+
+#[test]
+fn test_resample() {
+    let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+    let a: TimeSeries = vec![(date(2000, 1, 15), 1.0), (date(2000, 2, 15), 3.0)]
+        .into_iter()
+        .collect();
+    let b: TimeSeries = vec![(date(2000, 5, 1), 5.0)].into_iter().collect();
+
+    let rows = resample(&[&a, &b], Grid::Quarterly);
+    assert_eq!(
+        rows,
+        vec![
+            (date(2000, 1, 1), vec![Some(2.0), None]),
+            (date(2000, 4, 1), vec![None, Some(5.0)]),
+        ]
+    );
+    assert_eq!(resample(&[&a, &b], Grid::Monthly).len(), 5);
+}
@@ -0,0 +1,67 @@
+//! Lomb-Scargle periodogram, which estimates spectral power directly from unevenly spaced
+//! samples rather than needing them resampled onto a regular grid first.
+
+use std::f64::consts::PI;
+
+/// Normalised Lomb-Scargle power of `ys` sampled at times `ts` (in years) at each of `periods`
+/// (also in years).
+pub fn lomb_scargle(ts: &[f64], ys: &[f64], periods: &[f64]) -> Vec<f64> {
+    let n = ys.len() as f64;
+    let mean = ys.iter().sum::<f64>() / n;
+    let variance = ys.iter().map(|y| (y - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    if variance.is_nan() || variance <= 0.0 {
+        return vec![0.0; periods.len()];
+    }
+
+    periods
+        .iter()
+        .map(|period| {
+            let omega = 2.0 * PI / period;
+            let (sin2, cos2) = ts.iter().fold((0.0, 0.0), |(s, c), t| {
+                (s + (2.0 * omega * t).sin(), c + (2.0 * omega * t).cos())
+            });
+            let tau = sin2.atan2(cos2) / (2.0 * omega);
+
+            let (mut ycos, mut ysin, mut cc, mut ss) = (0.0, 0.0, 0.0, 0.0);
+            for (t, y) in ts.iter().zip(ys) {
+                let (sin, cos) = (omega * (t - tau)).sin_cos();
+                ycos += (y - mean) * cos;
+                ysin += (y - mean) * sin;
+                cc += cos * cos;
+                ss += sin * sin;
+            }
+            let cos_term = if cc > 0.0 { ycos * ycos / cc } else { 0.0 };
+            let sin_term = if ss > 0.0 { ysin * ysin / ss } else { 0.0 };
+            (cos_term + sin_term) / (2.0 * variance)
+        })
+        .collect()
+}
+
+/// `samples` periods spaced evenly on a log scale between `min` and `max`.
+pub fn log_spaced(min: f64, max: f64, samples: usize) -> Vec<f64> {
+    if samples < 2 {
+        return vec![min];
+    }
+    let (log_min, log_max) = (min.ln(), max.ln());
+    (0..samples)
+        .map(|i| (log_min + (log_max - log_min) * i as f64 / (samples - 1) as f64).exp())
+        .collect()
+}
+
+#[test]
+fn test_lomb_scargle_finds_period() {
+    // Unevenly spaced samples of a five year cycle
+    let ts: Vec<f64> = (0..200)
+        .map(|i| i as f64 * 0.25 + (i as f64 * 0.7).sin() * 0.1)
+        .collect();
+    let ys: Vec<f64> = ts.iter().map(|t| (2.0 * PI * t / 5.0).sin()).collect();
+
+    let periods = log_spaced(1.0, 20.0, 400);
+    let power = lomb_scargle(&ts, &ys, &periods);
+    let (peak, _) = periods
+        .iter()
+        .zip(&power)
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .unwrap();
+    assert!((peak - 5.0).abs() < 0.1);
+}
@@ -0,0 +1,169 @@
+//! Seasonal-trend decomposition using loess, after Cleveland et al. (1990). STL: A
+//! Seasonal-Trend Decomposition Procedure Based on Loess. Journal of Official Statistics, 6(1),
+//! 3-73.
+//!
+//! Points are treated as equally spaced, so the period is a number of points rather than a
+//! duration.
+
+use chrono::NaiveDate;
+
+use super::sampling_interval;
+
+const INNER_ITERATIONS: usize = 2;
+
+pub struct Decomposition {
+    pub trend: Vec<f64>,
+    pub seasonal: Vec<f64>,
+    pub residual: Vec<f64>,
+}
+
+/// Guesses how many points make up a year from the typical spacing between them.
+pub fn detect_period(points: &[(NaiveDate, f32)]) -> Option<usize> {
+    let interval = sampling_interval(points)?;
+    let period = (365.25 / interval).round() as usize;
+    (period >= 2).then_some(period)
+}
+
+pub fn decompose(ys: &[f64], period: usize) -> Decomposition {
+    let n = ys.len();
+    let seasonal_span = 7;
+    let low_pass_span = next_odd(period as f64);
+    let trend_span = next_odd(1.5 * period as f64 / (1.0 - 1.5 / seasonal_span as f64));
+
+    let mut trend = vec![0.0; n];
+    let mut seasonal = vec![0.0; n];
+    for _ in 0..INNER_ITERATIONS {
+        let detrended: Vec<f64> = ys.iter().zip(&trend).map(|(y, t)| y - t).collect();
+
+        // Smooth each cycle-subseries, extending it by one period at either end
+        let mut cycle = vec![0.0; n + 2 * period];
+        for phase in 0..period {
+            let subseries: Vec<f64> = detrended
+                .iter()
+                .skip(phase)
+                .step_by(period)
+                .copied()
+                .collect();
+            if subseries.is_empty() {
+                continue;
+            }
+            for k in 0..subseries.len() + 2 {
+                let index = phase + k * period;
+                if index < cycle.len() {
+                    cycle[index] = loess(&subseries, seasonal_span, k as f64 - 1.0);
+                }
+            }
+        }
+
+        // Remove any trend that leaked into the cycle-subseries
+        let low_pass = moving_average(&moving_average(&moving_average(&cycle, period), period), 3);
+        let low_pass: Vec<f64> = (0..n)
+            .map(|i| loess(&low_pass, low_pass_span, i as f64))
+            .collect();
+        for i in 0..n {
+            seasonal[i] = cycle[period + i] - low_pass[i];
+        }
+
+        let deseasonalized: Vec<f64> = ys.iter().zip(&seasonal).map(|(y, s)| y - s).collect();
+        trend = (0..n)
+            .map(|i| loess(&deseasonalized, trend_span, i as f64))
+            .collect();
+    }
+
+    let residual = (0..n).map(|i| ys[i] - seasonal[i] - trend[i]).collect();
+    Decomposition {
+        trend,
+        seasonal,
+        residual,
+    }
+}
+
+fn next_odd(x: f64) -> usize {
+    let n = x.ceil() as usize;
+    if n.is_multiple_of(2) {
+        n + 1
+    } else {
+        n
+    }
+}
+
+fn moving_average(ys: &[f64], width: usize) -> Vec<f64> {
+    if ys.len() < width {
+        return vec![];
+    }
+    ys.windows(width)
+        .map(|window| window.iter().sum::<f64>() / width as f64)
+        .collect()
+}
+
+/// Locally weighted linear regression of `ys` (at positions `0..ys.len()`) evaluated at `x`,
+/// using the `span` nearest points with tricube weights.
+fn loess(ys: &[f64], span: usize, x: f64) -> f64 {
+    let n = ys.len();
+    match n {
+        0 => return 0.0,
+        1 => return ys[0],
+        _ => {}
+    }
+
+    let span = span.min(n);
+    let left = (x.round() as isize - span as isize / 2).clamp(0, (n - span) as isize) as usize;
+    let right = left + span - 1;
+    let mut bandwidth = (x - left as f64).max(right as f64 - x);
+    if span < ys.len() {
+        bandwidth = bandwidth.max(1.0);
+    } else {
+        bandwidth += 1.0;
+    }
+
+    let weights: Vec<(f64, f64, f64)> = (left..=right)
+        .map(|i| {
+            let distance = ((i as f64 - x) / bandwidth).abs();
+            let weight = if distance < 1.0 {
+                (1.0 - distance.powi(3)).powi(3)
+            } else {
+                0.0
+            };
+            (i as f64, ys[i], weight)
+        })
+        .collect();
+
+    let total: f64 = weights.iter().map(|(_, _, w)| w).sum();
+    if total <= 0.0 {
+        return ys[x.round().clamp(0.0, (n - 1) as f64) as usize];
+    }
+    let mean_x = weights.iter().map(|(xi, _, w)| w * xi).sum::<f64>() / total;
+    let mean_y = weights.iter().map(|(_, yi, w)| w * yi).sum::<f64>() / total;
+    let (sxx, sxy) = weights.iter().fold((0.0, 0.0), |(sxx, sxy), (xi, yi, w)| {
+        (
+            sxx + w * (xi - mean_x).powi(2),
+            sxy + w * (xi - mean_x) * (yi - mean_y),
+        )
+    });
+    let slope = if sxx > f64::EPSILON { sxy / sxx } else { 0.0 };
+    mean_y + slope * (x - mean_x)
+}
+
+#[test]
+fn test_decompose() {
+    let period = 4;
+    let cycle = [3.0, -1.0, -3.0, 1.0];
+    let ys: Vec<f64> = (0..80)
+        .map(|i| 0.5 * i as f64 + cycle[i % period])
+        .collect();
+
+    let decomposition = decompose(&ys, period);
+    for i in 10..70 {
+        assert!((decomposition.trend[i] - 0.5 * i as f64).abs() < 0.1);
+        assert!((decomposition.seasonal[i] - cycle[i % period]).abs() < 0.1);
+        assert!(decomposition.residual[i].abs() < 0.1);
+    }
+}
+
+#[test]
+fn test_detect_period() {
+    let points: Vec<(NaiveDate, f32)> = (0..12)
+        .map(|i| (NaiveDate::from_ymd_opt(2000, 1 + i, 15).unwrap(), 0.0))
+        .collect();
+    assert_eq!(detect_period(&points), Some(12));
+}
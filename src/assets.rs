@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use actix_files::{file_extension_to_mime, NamedFile};
+use actix_web::{
+    error, get,
+    http::header::{self, AcceptEncoding, ContentEncoding, ContentType, Encoding},
+    web::{self, Bytes},
+    CustomizeResponder, Either, HttpMessage, HttpRequest, HttpResponse, Responder, Result,
+};
+use flate2::{write::GzEncoder, Compression};
+use once_cell::sync::Lazy;
+use tracing::info;
+
+use crate::caching::{self, Encoded};
+use crate::{embedded, graphs};
+
+/// Extensions of files worth compressing, the rest (images, woff2) are compressed already.
+const COMPRESSIBLE: &[&str] = &["css", "html", "js", "json", "svg", "ttf", "txt", "wasm"];
+
+/// Files smaller than this gain too little to be worth a second copy.
+const MIN_SIZE: u64 = 1024;
+
+/// Writes `.br` and `.gz` copies of each compressible file under `dir`, keeping only those that
+/// come out smaller than the original.
+pub fn precompress(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            precompress(&path)?;
+            continue;
+        }
+        let compressible = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| COMPRESSIBLE.contains(&ext));
+        if !compressible || fs::metadata(&path)?.len() < MIN_SIZE {
+            continue;
+        }
+
+        let original = fs::read(&path)?;
+        let mut gzip = GzEncoder::new(vec![], Compression::best());
+        gzip.write_all(&original)?;
+        let mut brotli = brotli::CompressorWriter::new(vec![], 4096, 11, 22);
+        brotli.write_all(&original)?;
+
+        for (ext, compressed) in [("gz", gzip.finish()?), ("br", brotli.into_inner())] {
+            let variant = variant_path(&path, ext);
+            if compressed.len() < original.len() {
+                File::create(&variant)?.write_all(&compressed)?;
+                info!(
+                    "{}: {} bytes, {} as {}",
+                    path.display(),
+                    original.len(),
+                    compressed.len(),
+                    ext
+                );
+            } else if variant.exists() {
+                fs::remove_file(&variant)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn variant_path(path: &Path, ext: &str) -> PathBuf {
+    let mut variant = path.as_os_str().to_owned();
+    variant.push(".");
+    variant.push(ext);
+    PathBuf::from(variant)
+}
+
+/// Where the files served under /s are.
+#[derive(Clone, Debug)]
+pub struct StaticDir(pub PathBuf);
+
+#[get("/s/{path:.*}")]
+pub async fn static_file(
+    req: HttpRequest,
+    dir: web::Data<StaticDir>,
+    path: web::Path<String>,
+) -> Result<impl Responder> {
+    serve(&req, &dir, &path)
+}
+
+/// The files built into the binary, tagged once rather than on every request for them.
+static EMBEDDED: Lazy<HashMap<&'static Path, Encoded>> = Lazy::new(|| {
+    embedded::static_files()
+        .into_iter()
+        .map(|(path, contents)| (path, Encoded::new(Bytes::from_static(contents))))
+        .collect()
+});
+
+/// Serves `path` under `dir`, preferring a precompressed `.br` or `.gz` copy when there is
+/// one and the client accepts it. Files missing from disk are served from the copies built into
+/// the binary, if there are any.
+pub fn serve(
+    req: &HttpRequest,
+    dir: &StaticDir,
+    path: &str,
+) -> Result<Either<CustomizeResponder<NamedFile>, HttpResponse>> {
+    let not_found = || error::ErrorNotFound(format!("no file at {path}"));
+
+    // Only plain names, so that nothing outside the directory can be reached
+    let relative: PathBuf = Path::new(path).components().collect();
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(not_found());
+    }
+    let file = dir.0.join(&relative);
+    if !file.is_file() {
+        let encoded = EMBEDDED.get(relative.as_path()).ok_or_else(not_found)?;
+        let ext = relative.extension().and_then(|ext| ext.to_str());
+        return Ok(Either::Right(caching::respond(
+            req,
+            "no-cache",
+            *graphs::STARTED,
+            ContentType(file_extension_to_mime(ext.unwrap_or_default())),
+            encoded,
+        )));
+    }
+
+    let variants = [
+        (Encoding::brotli(), "br", ContentEncoding::Brotli),
+        (Encoding::gzip(), "gz", ContentEncoding::Gzip),
+    ];
+    let mut supported = vec![Encoding::identity()];
+    supported.extend(
+        variants
+            .iter()
+            .filter(|(_, ext, _)| variant_path(&file, ext).is_file())
+            .map(|(encoding, _, _)| encoding.clone()),
+    );
+    let accepted = req
+        .get_header::<AcceptEncoding>()
+        .and_then(|accept| accept.negotiate(supported.iter()));
+
+    let variant = variants
+        .iter()
+        .find(|(encoding, _, _)| Some(encoding) == accepted.as_ref());
+    let named = match variant {
+        // Named after the original, so that it gets the original's content type
+        Some((_, ext, content_encoding)) => {
+            NamedFile::from_file(File::open(variant_path(&file, ext))?, &file)?
+                .set_content_encoding(*content_encoding)
+        }
+        None => NamedFile::open(&file)?,
+    };
+
+    Ok(Either::Left(
+        named
+            .customize()
+            .insert_header((header::VARY, "Accept-Encoding")),
+    ))
+}
+
+#[test]
+fn test_variant_path() {
+    assert_eq!(
+        variant_path(Path::new("static/viewer_bg.wasm"), "br"),
+        PathBuf::from("static/viewer_bg.wasm.br")
+    );
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use actix_web::{
+    http::header::{self, ContentType, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch},
+    web::Bytes,
+    HttpMessage, HttpRequest, HttpResponse,
+};
+
+/// Routes whose `Cache-Control` can be set with `--cache-control`, and what they send by
+/// default. Datasets only change when the server restarts, but the index is always revalidated
+/// so that clients find out about new ones.
+const DEFAULT_POLICIES: &[(&str, &str)] = &[
+    ("list_graphs", "no-cache"),
+    ("show_graph", "public, max-age=300"),
+    ("batch_graphs", "public, max-age=300"),
+    ("show_chart", "public, max-age=300"),
+    ("show_openapi", "no-cache"),
+];
+
+#[derive(Clone, Debug)]
+pub struct CachePolicies(HashMap<String, String>);
+
+impl CachePolicies {
+    /// The default policies, with `overrides` of (route, policy) applied on top.
+    pub fn new(overrides: &[(String, String)]) -> Self {
+        let mut policies: HashMap<String, String> = DEFAULT_POLICIES
+            .iter()
+            .map(|(route, policy)| (route.to_string(), policy.to_string()))
+            .collect();
+        policies.extend(overrides.iter().cloned());
+        CachePolicies(policies)
+    }
+
+    pub fn get(&self, route: &str) -> &str {
+        self.0.get(route).map(String::as_str).unwrap_or("no-cache")
+    }
+}
+
+/// Parses a `ROUTE=POLICY` command line argument.
+pub fn parse_policy(arg: &str) -> Result<(String, String), String> {
+    let (route, policy) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected ROUTE=POLICY, got {arg}"))?;
+    if !DEFAULT_POLICIES.iter().any(|(name, _)| *name == route) {
+        let routes: Vec<&str> = DEFAULT_POLICIES.iter().map(|(name, _)| *name).collect();
+        return Err(format!(
+            "unknown route {route}, expected one of {}",
+            routes.join(", ")
+        ));
+    }
+    Ok((route.to_string(), policy.to_string()))
+}
+
+/// A response body along with its validator, so that both can be worked out once and sent many
+/// times. Cloning only bumps a reference count.
+#[derive(Clone, Debug)]
+pub struct Encoded {
+    pub body: Bytes,
+    pub etag: EntityTag,
+}
+
+impl Encoded {
+    pub fn new(body: impl Into<Bytes>) -> Self {
+        let body = body.into();
+        Encoded {
+            etag: etag(&body),
+            body,
+        }
+    }
+}
+
+/// A validator for `body`, which changes whenever any byte of it does. It is weak because the same
+/// tag is sent for every content coding the compression middleware might apply to the body.
+pub fn etag(body: &[u8]) -> EntityTag {
+    EntityTag::new_weak(blake3::hash(body).to_hex()[..32].to_string())
+}
+
+/// Whether the validators the client sent show that it already has the response tagged `etag`.
+/// `If-Modified-Since` is only looked at when there is no `If-None-Match`.
+pub fn unchanged(req: &HttpRequest, etag: &EntityTag, modified: SystemTime) -> bool {
+    match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        None => req
+            .get_header::<IfModifiedSince>()
+            .is_some_and(|IfModifiedSince(since)| {
+                SystemTime::from(HttpDate::from(modified)) <= SystemTime::from(since)
+            }),
+    }
+}
+
+/// Responds with `body`, or with 304 Not Modified if the validators the client sent show that it
+/// already has it.
+pub fn respond(
+    req: &HttpRequest,
+    policy: &str,
+    modified: SystemTime,
+    content_type: ContentType,
+    encoded: &Encoded,
+) -> HttpResponse {
+    let etag = &encoded.etag;
+    let unchanged = unchanged(req, etag, modified);
+    let modified = HttpDate::from(modified);
+
+    let mut response = if unchanged {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response
+        .insert_header(header::ETag(etag.clone()))
+        .insert_header(header::LastModified(modified))
+        .insert_header((header::CACHE_CONTROL, policy.to_string()));
+    if unchanged {
+        response.finish()
+    } else {
+        response
+            .content_type(content_type)
+            .body(encoded.body.clone())
+    }
+}
+
+#[test]
+fn test_conditional_requests() {
+    use actix_web::{http::StatusCode, test::TestRequest};
+
+    let body = Encoded::new(b"graph".to_vec());
+    let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+    let send = |req: HttpRequest| {
+        respond(
+            &req,
+            "no-cache",
+            modified,
+            ContentType::octet_stream(),
+            &body,
+        )
+        .status()
+    };
+
+    assert_eq!(
+        send(TestRequest::default().to_http_request()),
+        StatusCode::OK
+    );
+    let tag = body.etag.to_string();
+    // Weak, since compression changes the bytes sent but not the tag
+    assert!(tag.starts_with("W/\""));
+    let matching = TestRequest::default().insert_header((header::IF_NONE_MATCH, tag.as_str()));
+    assert_eq!(send(matching.to_http_request()), StatusCode::NOT_MODIFIED);
+    let stale = TestRequest::default().insert_header((header::IF_NONE_MATCH, "\"other\""));
+    assert_eq!(send(stale.to_http_request()), StatusCode::OK);
+
+    let since = HttpDate::from(modified).to_string();
+    let later = TestRequest::default().insert_header((header::IF_MODIFIED_SINCE, since.as_str()));
+    assert_eq!(send(later.to_http_request()), StatusCode::NOT_MODIFIED);
+    // A matching date does not count when the tags disagree
+    let both = TestRequest::default()
+        .insert_header((header::IF_NONE_MATCH, "\"other\""))
+        .insert_header((header::IF_MODIFIED_SINCE, since.as_str()));
+    assert_eq!(send(both.to_http_request()), StatusCode::OK);
+}
@@ -0,0 +1,347 @@
+use std::fs::{self, File};
+use std::io::{self, Error, ErrorKind, IsTerminal, Write};
+use std::ops::Bound;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use tracing::warn;
+
+use shared::series::Point;
+
+use crate::analysis::quality;
+use crate::graphs::{self, Source};
+use crate::plot::{braille, Series};
+
+/// How `export` writes a dataset.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum ExportFormat {
+    /// `Date` and `Value` columns, as the datasets are kept
+    #[default]
+    Csv,
+    /// The same as the API sends with `format=json`
+    Json,
+}
+
+/// Parses each of `files`, or every dataset in the catalog when there are none, and prints what
+/// looks wrong in them. Fails if any of them could not be read at all.
+pub fn validate(files: &[PathBuf]) -> io::Result<()> {
+    // Datasets are read from wherever the server would load them, which may be the binary
+    let targets: Vec<(String, PathBuf, io::Result<Vec<Point>>)> = if files.is_empty() {
+        graphs::catalog()?
+            .into_iter()
+            .map(|source| {
+                let path = graphs::data_path(&source.path);
+                let points = graphs::open_data(&source.path).and_then(graphs::read_points);
+                (source.name, path, points)
+            })
+            .collect()
+    } else {
+        files
+            .iter()
+            .map(|file| {
+                let points = File::open(file).and_then(graphs::read_points);
+                (file.display().to_string(), file.clone(), points)
+            })
+            .collect()
+    };
+
+    let mut stdout = io::stdout().lock();
+    let mut unreadable = 0;
+    for (label, path, points) in targets {
+        match points {
+            Ok(points) => {
+                let issues = quality::check(&points);
+                writeln!(
+                    stdout,
+                    "{}: {} points, {} issues",
+                    label,
+                    points.len(),
+                    issues.len()
+                )?;
+                for issue in issues {
+                    writeln!(
+                        stdout,
+                        "{}: point {} ({}, {:?}): {}",
+                        label, issue.index, issue.date, issue.kind, issue.message
+                    )?;
+                }
+            }
+            Err(e) => {
+                writeln!(
+                    stdout,
+                    "{}: could not read {}: {}",
+                    label,
+                    path.display(),
+                    e
+                )?;
+                unreadable += 1;
+            }
+        }
+    }
+    if unreadable > 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{unreadable} could not be read"),
+        ));
+    }
+    Ok(())
+}
+
+/// Prints the catalog as tab separated columns.
+pub fn list() -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "Name\tPath\tColor\tDescription")?;
+    for source in graphs::catalog()? {
+        let (r, g, b) = source.color;
+        writeln!(
+            stdout,
+            "{}\t{}\t{:02X}{:02X}{:02X}\t{}",
+            source.name, source.path, r, g, b, source.description
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the dataset called `name` to stdout.
+pub fn export(name: &str, format: ExportFormat) -> io::Result<()> {
+    let dataset = graphs::load_named(name)?;
+
+    let mut stdout = io::stdout().lock();
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(stdout);
+            writer.write_record(["Date", "Value"])?;
+            for (date, value) in dataset.graph.points.iter() {
+                writer.write_record([date.to_string(), value.to_string()])?;
+            }
+            writer.flush()
+        }
+        ExportFormat::Json => {
+            stdout.write_all(&dataset.json.body)?;
+            writeln!(stdout)
+        }
+    }
+}
+
+/// Draws the datasets called `names` between `from` and `to` on stdout, `width` by `height`
+/// characters, which default to the size of the terminal as far as the environment says.
+pub fn plot(
+    names: &[String],
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    width: Option<usize>,
+    height: Option<usize>,
+) -> io::Result<()> {
+    let datasets = names
+        .iter()
+        .map(|name| graphs::load_named(name))
+        .collect::<io::Result<Vec<_>>>()?;
+    let range = (
+        from.map_or(Bound::Unbounded, Bound::Included),
+        to.map_or(Bound::Unbounded, Bound::Included),
+    );
+    let series: Vec<Series> = datasets
+        .iter()
+        .map(|dataset| Series {
+            name: &dataset.graph.name,
+            color: dataset.graph.color,
+            points: dataset.graph.points.range(range),
+        })
+        .collect();
+
+    let dates = series
+        .iter()
+        .flat_map(|series| series.points.iter().map(|(date, _)| *date));
+    let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "none of the datasets have points between those dates",
+        ));
+    };
+    let size = |given: Option<usize>, variable: &str, default: usize| {
+        given
+            .or_else(|| std::env::var(variable).ok()?.parse().ok())
+            .unwrap_or(default)
+    };
+    let size = (size(width, "COLUMNS", 100), size(height, "LINES", 30));
+    let color = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+    let plot = braille::render(
+        &series,
+        from.unwrap_or(first),
+        to.unwrap_or(last),
+        size,
+        color,
+    );
+    io::stdout().lock().write_all(plot.as_bytes())
+}
+
+/// How `import` describes a dataset in the catalog.
+#[derive(clap::Args, Debug)]
+pub struct NewDataset {
+    #[arg(long)]
+    pub name: String,
+    #[arg(long, default_value = "")]
+    pub description: String,
+    /// What the values are measured in, e.g. mm
+    #[arg(long, default_value = "")]
+    pub units: String,
+    /// Where the data comes from, shown under charts of it
+    #[arg(long, default_value = "")]
+    pub citation: String,
+    /// Within the data directory, without the .tsv [default: imported/NAME]
+    #[arg(long)]
+    pub path: Option<String>,
+    /// As RRGGBB
+    #[arg(long, value_parser = parse_color, default_value = "EAFDCF")]
+    pub color: (u8, u8, u8),
+}
+
+/// Copies the TSV `file` into the data directory and adds it to the catalog, after checking
+/// that it can be read and that it doesn't clash with a dataset that's there already.
+pub fn import(file: &Path, dataset: &NewDataset) -> io::Result<()> {
+    if dataset.name.trim().is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "the name is empty"));
+    }
+    let path = match &dataset.path {
+        Some(path) => path.clone(),
+        None if slug(&dataset.name)
+            .chars()
+            .any(|c| c.is_ascii_alphanumeric()) =>
+        {
+            format!("imported/{}", slug(&dataset.name))
+        }
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "no path can be made from the name {}, give one",
+                    dataset.name
+                ),
+            ))
+        }
+    };
+
+    let points = graphs::read_points(File::open(file)?)?;
+    if points.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{} has no points", file.display()),
+        ));
+    }
+    for issue in quality::check(&points) {
+        warn!(
+            "{}: point {} ({:?}): {}",
+            file.display(),
+            issue.index,
+            issue.kind,
+            issue.message
+        );
+    }
+
+    if !Path::new(&path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{path} is not a path within the data directory"),
+        ));
+    }
+
+    let mut catalog = graphs::catalog()?;
+    if let Some(clash) = catalog
+        .iter()
+        .find(|source| source.name == dataset.name || source.path == path)
+    {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("the catalog already has {} at {}", clash.name, clash.path),
+        ));
+    }
+    let destination = graphs::data_path(&path);
+    if destination.exists() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} already exists", destination.display()),
+        ));
+    }
+
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(file, &destination)?;
+    println!(
+        "{}: {} points in {}",
+        dataset.name,
+        points.len(),
+        destination.display()
+    );
+    catalog.push(Source {
+        name: dataset.name.clone(),
+        description: dataset.description.clone(),
+        units: dataset.units.clone(),
+        citation: dataset.citation.clone(),
+        path,
+        color: dataset.color,
+    });
+    graphs::save_catalog(&catalog)
+}
+
+/// A file name for a dataset called `name`.
+fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .collect()
+}
+
+/// Parses a color written as hex RGB, e.g. `B1F8F2`.
+pub fn parse_color(arg: &str) -> Result<(u8, u8, u8), String> {
+    let hex = arg.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+    };
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok((r, g, b)),
+        _ => Err(format!("expected a color as RRGGBB, got {arg}")),
+    }
+}
+
+#[test]
+fn test_slug() {
+    assert_eq!(slug("NOAA Global"), "noaa-global");
+    assert_eq!(slug("../CSIRO"), "---csiro");
+}
+
+#[test]
+fn test_import_names() {
+    let dataset = |name: &str| NewDataset {
+        name: name.to_string(),
+        description: String::new(),
+        units: String::new(),
+        citation: String::new(),
+        path: None,
+        color: (0xEA, 0xFD, 0xCF),
+    };
+    // Turned away before the file is even looked at
+    for name in ["", "  ", "∆ / ∇"] {
+        let e = import(Path::new("missing.tsv"), &dataset(name)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput, "{name:?}");
+    }
+    let e = import(Path::new("missing.tsv"), &dataset("Named")).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::NotFound);
+}
+
+#[test]
+fn test_parse_color() {
+    assert_eq!(parse_color("B1F8F2"), Ok((0xB1, 0xF8, 0xF2)));
+    assert_eq!(parse_color("#bcd39c"), Ok((0xBC, 0xD3, 0x9C)));
+    assert!(parse_color("B1F8").is_err());
+    assert!(parse_color("B1F8G2").is_err());
+}
@@ -0,0 +1,45 @@
+//! Copies of `static/` and `data/` built into the binary with the `embed` feature, for whatever
+//! is not found on disk.
+
+use std::path::Path;
+
+#[cfg(feature = "embed")]
+use include_dir::{include_dir, Dir, DirEntry, File};
+
+#[cfg(feature = "embed")]
+static STATIC: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/static");
+
+#[cfg(feature = "embed")]
+static DATA: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/data");
+
+/// Every file built in from `static/`, by its path within it.
+#[cfg(feature = "embed")]
+pub fn static_files() -> Vec<(&'static Path, &'static [u8])> {
+    fn walk(dir: &'static Dir<'static>, files: &mut Vec<(&'static Path, &'static [u8])>) {
+        for entry in dir.entries() {
+            match entry {
+                DirEntry::Dir(dir) => walk(dir, files),
+                DirEntry::File(file) => files.push((file.path(), file.contents())),
+            }
+        }
+    }
+    let mut files = vec![];
+    walk(&STATIC, &mut files);
+    files
+}
+
+#[cfg(not(feature = "embed"))]
+pub fn static_files() -> Vec<(&'static Path, &'static [u8])> {
+    vec![]
+}
+
+/// The built in copy of `path` within `data/`.
+#[cfg(feature = "embed")]
+pub fn data_file(path: &Path) -> Option<&'static [u8]> {
+    DATA.get_file(path).map(File::contents)
+}
+
+#[cfg(not(feature = "embed"))]
+pub fn data_file(_path: &Path) -> Option<&'static [u8]> {
+    None
+}
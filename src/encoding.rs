@@ -0,0 +1,135 @@
+use actix_web::{error, http::header::ContentType, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use shared::envelope;
+use tracing::{error, info_span};
+use utoipa::ToSchema;
+
+/// The encoding a client asked for with `?format=`. Postcard is what the viewer speaks, JSON and
+/// CSV are for everyone else.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Postcard,
+    Json,
+    Csv,
+}
+
+impl Format {
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            Format::Postcard => ContentType::octet_stream(),
+            Format::Json => ContentType::json(),
+            Format::Csv => ContentType(actix_web::mime::TEXT_CSV_UTF_8),
+        }
+    }
+}
+
+/// What a chart is drawn as, from the extension of the route.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Svg,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            ImageFormat::Svg => ContentType(actix_web::mime::IMAGE_SVG),
+            ImageFormat::Png => ContentType::png(),
+        }
+    }
+}
+
+/// Encodes `value` as postcard, sealed in an [`envelope`] with the version of the types, or JSON.
+/// `what` describes it in errors. What CSV looks like depends on the shape of the data, so it is
+/// up to the caller to handle that.
+pub fn encode<T: Serialize>(format: Format, value: &T, what: &str) -> Result<HttpResponse> {
+    let (content_type, body) = encode_body(format, value, what)?;
+    Ok(HttpResponse::Ok().content_type(content_type).body(body))
+}
+
+/// The body [`encode`] would respond with, along with its content type.
+pub fn encode_body<T: Serialize>(
+    format: Format,
+    value: &T,
+    what: &str,
+) -> Result<(ContentType, Vec<u8>)> {
+    let _span = info_span!("encode", what, ?format).entered();
+    let body = match format {
+        Format::Postcard => envelope::seal(value).map_err(|e| e.to_string()),
+        Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+        Format::Csv => {
+            return Err(error::ErrorNotAcceptable(format!(
+                "{what} is not available as CSV"
+            )))
+        }
+    };
+    let body = body.map_err(|e| {
+        error!("error encoding {}: {}", what, e);
+        error::ErrorInternalServerError(format!("error encoding {what}"))
+    })?;
+    Ok((format.content_type(), body))
+}
+
+/// Joins values that are each already encoded as `format` into the encoding of a list of them,
+/// so that pre-encoded values can be sent together without encoding them again.
+pub fn encode_list(format: Format, items: &[&[u8]], what: &str) -> Result<Vec<u8>> {
+    let mut body = match format {
+        // One envelope around the list, in place of the ones around each item
+        Format::Postcard => envelope::seal(&items.len()).map_err(|e| {
+            error!("error encoding {}: {}", what, e);
+            error::ErrorInternalServerError(format!("error encoding {what}"))
+        })?,
+        Format::Json => b"[".to_vec(),
+        Format::Csv => {
+            return Err(error::ErrorNotAcceptable(format!(
+                "{what} is not available as CSV"
+            )))
+        }
+    };
+    for (i, item) in items.iter().enumerate() {
+        if format == Format::Json && i > 0 {
+            body.push(b',');
+        }
+        let item = match format {
+            Format::Postcard => envelope::body(item).map_err(|e| {
+                error!("error unwrapping an item of {}: {}", what, e);
+                error::ErrorInternalServerError(format!("error encoding {what}"))
+            })?,
+            _ => item,
+        };
+        body.extend_from_slice(item);
+    }
+    if format == Format::Json {
+        body.push(b']');
+    }
+    Ok(body)
+}
+
+pub fn csv_response(body: Vec<u8>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(Format::Csv.content_type())
+        .body(body)
+}
+
+#[test]
+fn test_encode_list() {
+    let values = vec![
+        (1u32, "one".to_string()),
+        (300, "three hundred".to_string()),
+    ];
+    for format in [Format::Postcard, Format::Json] {
+        let items: Vec<Vec<u8>> = values
+            .iter()
+            .map(|value| encode_body(format, value, "value").unwrap().1)
+            .collect();
+        let items: Vec<&[u8]> = items.iter().map(Vec::as_slice).collect();
+        assert_eq!(
+            encode_list(format, &items, "values").unwrap(),
+            encode_body(format, &values, "values").unwrap().1
+        );
+    }
+    assert!(encode_list(Format::Csv, &[], "values").is_err());
+}
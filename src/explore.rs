@@ -0,0 +1,465 @@
+//! A full screen terminal interface for looking through the datasets, much as the viewer does in
+//! the browser, that reads them from the data directory rather than from a server.
+
+use std::io::{self, Error, ErrorKind};
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate};
+use ratatui::crossterm::event::{self, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{self, Axis, Block, Chart, GraphType, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use shared::series::Point;
+
+use crate::analysis::{decimal_year, linear_fit, median};
+use crate::graphs::{self, Dataset};
+use crate::plot::ValueTicks;
+
+const HELP: &str = "↑↓ dataset  ←→ cursor  [ ] pan  + - zoom  0 reset  space overlay  q quit";
+
+/// The narrowest the plot can be zoomed to.
+const SHORTEST_VIEW: Duration = Duration::days(14);
+
+/// Browses every dataset in `INDEX` until the user quits.
+pub fn run() -> io::Result<()> {
+    let mut datasets: Vec<Arc<Dataset>> = graphs::INDEX.read().unwrap().values().cloned().collect();
+    if datasets.is_empty() {
+        return Err(Error::new(ErrorKind::NotFound, "no datasets loaded"));
+    }
+    datasets.sort_by(|a, b| a.graph.name.cmp(&b.graph.name));
+    let mut explorer = Explorer::new(datasets);
+    ratatui::run(|terminal| explorer.run(terminal))
+}
+
+struct Explorer {
+    datasets: Vec<Arc<Dataset>>,
+    list: ListState,
+    /// Which of the other datasets are drawn behind the selected one.
+    overlaid: Vec<bool>,
+    /// The dates shown in the plot.
+    view: (NaiveDate, NaiveDate),
+    /// The index of the point under the cursor, in the selected dataset.
+    cursor: usize,
+    quit: bool,
+}
+
+/// Summary statistics of the points in view.
+struct Stats {
+    count: usize,
+    min: f64,
+    max: f64,
+    mean: f64,
+    median: f64,
+    /// Change per decade, from a linear fit.
+    trend: f64,
+}
+
+impl Stats {
+    fn new(points: &[Point]) -> Option<Self> {
+        if points.is_empty() {
+            return None;
+        }
+        let xs: Vec<f64> = points.iter().map(|(date, _)| decimal_year(date)).collect();
+        let ys: Vec<f64> = points.iter().map(|(_, value)| *value as f64).collect();
+        let (_, slope) = linear_fit(&xs, &ys);
+        Some(Stats {
+            count: ys.len(),
+            min: ys.iter().copied().fold(f64::INFINITY, f64::min),
+            max: ys.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            mean: ys.iter().sum::<f64>() / ys.len() as f64,
+            median: median(&ys),
+            trend: slope * 10.0,
+        })
+    }
+}
+
+impl Explorer {
+    fn new(datasets: Vec<Arc<Dataset>>) -> Self {
+        let overlaid = vec![false; datasets.len()];
+        let mut explorer = Explorer {
+            datasets,
+            list: ListState::default(),
+            overlaid,
+            view: Default::default(),
+            cursor: 0,
+            quit: false,
+        };
+        explorer.select(0);
+        explorer
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Some(key) = event::read()?.as_key_press_event() {
+                self.key(key);
+            }
+        }
+        Ok(())
+    }
+
+    fn selected(&self) -> usize {
+        self.list.selected().unwrap_or(0)
+    }
+
+    fn points(&self) -> &[Point] {
+        self.datasets[self.selected()].graph.points.points()
+    }
+
+    /// Selects the dataset at `index`, showing all of it with the cursor on its last point.
+    fn select(&mut self, index: usize) {
+        self.list.select(Some(index));
+        self.cursor = self.points().len().saturating_sub(1);
+        self.view = self.extent();
+    }
+
+    /// The dates covered by the selected dataset and those overlaid on it.
+    fn extent(&self) -> (NaiveDate, NaiveDate) {
+        self.shown()
+            .map(|i| &self.datasets[i].graph.points)
+            .filter(|points| !points.is_empty())
+            .map(|points| (points.min_x(), points.max_x()))
+            .reduce(|(from, to), (min, max)| (from.min(min), to.max(max)))
+            .unwrap_or_default()
+    }
+
+    /// The datasets drawn, with the selected one last so that it is drawn on top.
+    fn shown(&self) -> impl Iterator<Item = usize> + '_ {
+        let selected = self.selected();
+        (0..self.datasets.len())
+            .filter(move |i| self.overlaid[*i] && *i != selected)
+            .chain([selected])
+    }
+
+    fn key(&mut self, key: KeyEvent) {
+        let span = self.view.1 - self.view.0;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.select(
+                self.selected()
+                    .checked_sub(1)
+                    .unwrap_or(self.datasets.len() - 1),
+            ),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.select((self.selected() + 1) % self.datasets.len())
+            }
+            KeyCode::Char(' ') => {
+                let selected = self.selected();
+                self.overlaid[selected] = !self.overlaid[selected];
+            }
+            KeyCode::Left | KeyCode::Char('h') => self.move_cursor(-1),
+            KeyCode::Right | KeyCode::Char('l') => self.move_cursor(1),
+            KeyCode::Char('[') => self.set_view(self.view.0 - span / 4, span),
+            KeyCode::Char(']') => self.set_view(self.view.0 + span / 4, span),
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                let span = (span / 2).max(SHORTEST_VIEW);
+                self.set_view(self.cursor_date() - span / 2, span)
+            }
+            KeyCode::Char('-') => {
+                let span = span * 2;
+                self.set_view(self.cursor_date() - span / 2, span)
+            }
+            KeyCode::Char('0') | KeyCode::Home => self.view = self.extent(),
+            _ => {}
+        }
+    }
+
+    fn cursor_date(&self) -> NaiveDate {
+        self.points()
+            .get(self.cursor)
+            .map_or(self.view.0, |(date, _)| *date)
+    }
+
+    /// Moves the cursor `by` points, panning to follow it if it leaves the view.
+    fn move_cursor(&mut self, by: isize) {
+        let last = self.points().len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(by).min(last);
+        let date = self.cursor_date();
+        let span = self.view.1 - self.view.0;
+        if date < self.view.0 {
+            self.set_view(date, span);
+        } else if date > self.view.1 {
+            self.set_view(date - span, span);
+        }
+    }
+
+    /// Shows `span` from `from`, kept within the data, and brings the cursor into view.
+    fn set_view(&mut self, from: NaiveDate, span: Duration) {
+        let (first, last) = self.extent();
+        self.view = if span >= last - first {
+            (first, last)
+        } else {
+            let from = from.clamp(first, last - span);
+            (from, from + span)
+        };
+
+        let points = self.points();
+        if points.is_empty() {
+            return;
+        }
+        let (from, to) = self.view;
+        if points[self.cursor].0 < from {
+            self.cursor = points
+                .partition_point(|(date, _)| *date < from)
+                .min(points.len() - 1);
+        } else if points[self.cursor].0 > to {
+            self.cursor = points
+                .partition_point(|(date, _)| *date <= to)
+                .saturating_sub(1);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [side, main] =
+            Layout::horizontal([Constraint::Length(36), Constraint::Min(0)]).areas(frame.area());
+        let [list, stats] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(9)]).areas(side);
+        let [plot, readout, help] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(main);
+
+        self.draw_list(frame, list);
+        self.draw_stats(frame, stats);
+        self.draw_plot(frame, plot);
+        self.draw_readout(frame, readout);
+        frame.render_widget(Line::from(HELP).dim(), help);
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .datasets
+            .iter()
+            .zip(&self.overlaid)
+            .map(|(dataset, overlaid)| {
+                let graph = &dataset.graph;
+                let marker = if *overlaid { "⣿⣿ " } else { "   " };
+                ListItem::new(vec![
+                    Line::from(vec![
+                        Span::styled(marker, Style::new().fg(rgb(graph.color))),
+                        Span::raw(graph.name.as_str()).bold(),
+                    ]),
+                    Line::from(format!("   {}", graph.description)).dim(),
+                ])
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("Datasets"))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_stats(&self, frame: &mut Frame, area: Rect) {
+        let (from, to) = self.view;
+        let lines = match Stats::new(self.datasets[self.selected()].graph.points.range(from..=to)) {
+            Some(stats) => vec![
+                Line::from(format!("Points  {}", stats.count)),
+                Line::from(format!("Min     {:.2}", stats.min)),
+                Line::from(format!("Max     {:.2}", stats.max)),
+                Line::from(format!("Mean    {:.2}", stats.mean)),
+                Line::from(format!("Median  {:.2}", stats.median)),
+                Line::from(format!("Trend   {:+.2} per decade", stats.trend)),
+            ],
+            None => vec![Line::from("No points in view").dim()],
+        };
+        let block = Block::bordered().title("In view");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_plot(&self, frame: &mut Frame, area: Rect) {
+        let (from, to) = self.view;
+        let shown: Vec<(&Dataset, Vec<(f64, f64)>)> = self
+            .shown()
+            .map(|i| {
+                let dataset = &*self.datasets[i];
+                let points = dataset.graph.points.range(from..=to);
+                let points = points
+                    .iter()
+                    .map(|(date, value)| (decimal_year(date), *value as f64))
+                    .collect();
+                (dataset, points)
+            })
+            .collect();
+
+        let values = shown
+            .iter()
+            .flat_map(|(_, points)| points.iter().map(|(_, y)| *y));
+        let min = values.clone().fold(f64::INFINITY, f64::min);
+        let max = values.fold(f64::NEG_INFINITY, f64::max);
+        let ticks = match min <= max {
+            true => ValueTicks::new(min, max, (area.height as usize / 4).max(2)),
+            false => ValueTicks::new(0.0, 1.0, 2),
+        };
+        let cursor = decimal_year(&self.cursor_date());
+        let cursor_line = [(cursor, ticks.min()), (cursor, ticks.max())];
+
+        let mut lines: Vec<widgets::Dataset> = shown
+            .iter()
+            .map(|(dataset, points)| {
+                widgets::Dataset::default()
+                    .name(dataset.graph.name.as_str())
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::new().fg(rgb(dataset.graph.color)))
+                    .data(points)
+            })
+            .collect();
+        lines.push(
+            widgets::Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::new().fg(Color::Gray))
+                .data(&cursor_line),
+        );
+
+        let middle = from + (to - from) / 2;
+        let date_format = if to - from > Duration::days(3 * 365) {
+            "%Y-%m"
+        } else {
+            "%Y-%m-%d"
+        };
+        let chart = Chart::new(lines)
+            .block(Block::bordered())
+            .x_axis(
+                Axis::default()
+                    .bounds([decimal_year(&from), decimal_year(&to)])
+                    .labels([from, middle, to].map(|date| date.format(date_format).to_string())),
+            )
+            .y_axis(
+                Axis::default()
+                    .bounds([ticks.min(), ticks.max()])
+                    .labels(ticks.values.iter().map(|value| ticks.label(*value))),
+            );
+        frame.render_widget(chart, area);
+    }
+
+    fn draw_readout(&self, frame: &mut Frame, area: Rect) {
+        let graph = &self.datasets[self.selected()].graph;
+        let line = match graph.points.points().get(self.cursor) {
+            Some((date, value)) => Line::from(vec![
+                Span::styled(graph.name.as_str(), Style::new().fg(rgb(graph.color))),
+                Span::raw(format!("  {date}  {value:.2}")),
+            ]),
+            None => Line::from(format!("{} has no points", graph.name)),
+        };
+        frame.render_widget(line, area);
+    }
+}
+
+fn rgb((r, g, b): (u8, u8, u8)) -> Color {
+    Color::Rgb(r, g, b)
+}
+
+#[cfg(test)]
+fn explorer() -> Explorer {
+    use shared::response::Graph;
+    use shared::series::TimeSeries;
+
+    let monthly = |name: &str, from: i32, months: u32, value: fn(u32) -> f32| {
+        let start = NaiveDate::from_ymd_opt(from, 1, 1).unwrap();
+        let points = (0..months)
+            .map(|i| (start + chrono::Months::new(i), value(i)))
+            .collect();
+        let graph = Graph {
+            name: name.to_string(),
+            description: format!("{name} sea level"),
+            units: "mm".to_string(),
+            citation: String::new(),
+            color: (0xB1, 0xF8, 0xF2),
+            points: TimeSeries::new(points),
+        };
+        Arc::new(Dataset::new(
+            graph,
+            vec![],
+            std::time::SystemTime::UNIX_EPOCH,
+        ))
+    };
+    Explorer::new(vec![
+        monthly("Rising", 1990, 240, |i| i as f32),
+        monthly("Flat", 2000, 120, |_| 5.0),
+    ])
+}
+
+#[cfg(test)]
+fn press(explorer: &mut Explorer, code: KeyCode) {
+    explorer.key(KeyEvent::new(code, KeyModifiers::NONE));
+}
+
+#[test]
+fn test_navigation() {
+    let date = |year, month| NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+    let mut explorer = explorer();
+    assert_eq!(explorer.view, (date(1990, 1), date(2009, 12)));
+    assert_eq!(explorer.cursor, 239);
+
+    // Zooming in halves the view around the cursor, kept within the data
+    press(&mut explorer, KeyCode::Char('+'));
+    let (from, to) = explorer.view;
+    assert_eq!(to, date(2009, 12));
+    assert_eq!(
+        (to - from).num_days(),
+        (date(2009, 12) - date(1990, 1)).num_days() / 2
+    );
+
+    // Panning left takes the cursor along with the view
+    press(&mut explorer, KeyCode::Char('['));
+    press(&mut explorer, KeyCode::Char('['));
+    assert!(explorer.view.0 < from);
+    assert!(explorer.cursor_date() <= explorer.view.1);
+
+    // The cursor moves by a point, and the view follows it
+    let cursor = explorer.cursor;
+    press(&mut explorer, KeyCode::Right);
+    assert_eq!(explorer.cursor, cursor + 1);
+    for _ in 0..240 {
+        press(&mut explorer, KeyCode::Right);
+    }
+    assert_eq!(explorer.cursor, 239);
+    assert_eq!(explorer.view.1, date(2009, 12));
+
+    press(&mut explorer, KeyCode::Char('0'));
+    assert_eq!(explorer.view, (date(1990, 1), date(2009, 12)));
+
+    // Selecting another dataset shows all of it, or all of both when it's overlaid
+    press(&mut explorer, KeyCode::Char(' '));
+    press(&mut explorer, KeyCode::Down);
+    assert_eq!(explorer.selected(), 1);
+    assert_eq!(explorer.view, (date(1990, 1), date(2009, 12)));
+    press(&mut explorer, KeyCode::Up);
+    press(&mut explorer, KeyCode::Char(' '));
+    press(&mut explorer, KeyCode::Down);
+    assert_eq!(explorer.view, (date(2000, 1), date(2009, 12)));
+
+    press(&mut explorer, KeyCode::Char('q'));
+    assert!(explorer.quit);
+}
+
+#[test]
+fn test_draw() {
+    let mut explorer = explorer();
+    let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(120, 30)).unwrap();
+    terminal.draw(|frame| explorer.draw(frame)).unwrap();
+    let screen: String = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect();
+
+    assert!(screen.contains("Rising sea level") && screen.contains("Flat sea level"));
+    assert!(screen.contains("Rising  2009-12-01  239.00"));
+    assert!(screen.contains("Points  240"));
+    assert!(screen.contains("Trend   +120.00 per decade"));
+    assert!(screen.contains("1990-01") && screen.contains("2009-12"));
+    assert!(screen
+        .chars()
+        .any(|c| ('\u{2801}'..='\u{28FF}').contains(&c)));
+}
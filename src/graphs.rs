@@ -1,42 +1,249 @@
-use std::sync::RwLock;
-use std::{collections::HashMap, fs::File, io::Result};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Instant, SystemTime};
+use std::{collections::HashMap, fs::File, io::Read};
 
 use chrono::NaiveDate;
-use once_cell::sync::Lazy;
-use serde::Deserialize;
-
-use shared::response::{Graph, Points};
-
-static GRAPHS: Lazy<RwLock<Vec<Graph>>> = Lazy::new(|| {
-    RwLock::new(vec![
-        Graph{
-            name: "CSIRO",
-            description: "Change in sea level in millimeters compared to the 1993-2008 average from the sea level group of CSIRO (Commonwealth Scientific and Industrial Research Organisation), Australia's national science agency. It is based on the paper Church, J. A., & White, N. J. (2011). Sea-Level Rise from the Late 19th to the Early 21st Century. Surveys in Geophysics, 32(4), 585Ð602. https://doi.org/10.1007/s10712-011-9119-1.",
-            points: points_from_tsv("sealevel/csiro").unwrap(),
-            color: (0xB1, 0xF8, 0xF2),
-        },
-        Graph{
-            name: "UHSLC",
-            description: "Change in sea level in millimeters compared to the 1993-2008 average from the University of Hawaii Sea Level Center (http://uhslc.soest.hawaii.edu/data/?fd). It is based on a weighted average of 373 global tide gauge records collected by the U.S. National Ocean Service, UHSLC, and partner agencies worldwide.",
-            points: points_from_tsv("sealevel/uhslc").unwrap(),
-            color: (0xBC, 0xD3, 0x9C),
-        },
-    ])
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info_span, warn};
+
+use shared::envelope;
+use shared::response::{Graph, GraphData, Issue};
+use shared::series::{Point, TimeSeries};
+
+use crate::analysis::quality;
+use crate::caching::Encoded;
+use crate::embedded;
+use crate::encoding::Format;
+use crate::metrics;
+
+/// A graph along with when the data behind it last changed, and how `show_graph` sends it.
+#[derive(Debug)]
+pub struct Dataset {
+    pub graph: Graph,
+    pub modified: SystemTime,
+    /// The graph as [`GraphData`], encoded once when it is loaded rather than on every request.
+    pub postcard: Encoded,
+    pub json: Encoded,
+    /// Problems with the points as they were loaded, before they were sorted and deduplicated.
+    pub issues: Vec<Issue>,
+}
+
+impl Dataset {
+    pub fn new(graph: Graph, issues: Vec<Issue>, modified: SystemTime) -> Self {
+        let _span = info_span!("encode dataset", dataset = graph.name.as_str()).entered();
+        let data = graph_data(&graph);
+        Dataset {
+            postcard: Encoded::new(envelope::seal(&data).expect("graph encodes as postcard")),
+            json: Encoded::new(serde_json::to_vec(&data).expect("graph encodes as JSON")),
+            graph,
+            modified,
+            issues,
+        }
+    }
+
+    /// The graph as it is sent, for when it needs changing first.
+    pub fn data(&self) -> GraphData {
+        graph_data(&self.graph)
+    }
+
+    /// The graph pre-encoded as `format`, if it can be sent that way.
+    pub fn encoded(&self, format: Format) -> Option<&Encoded> {
+        match format {
+            Format::Postcard => Some(&self.postcard),
+            Format::Json => Some(&self.json),
+            Format::Csv => None,
+        }
+    }
+}
+
+fn graph_data(graph: &Graph) -> GraphData {
+    GraphData {
+        name: graph.name.to_string(),
+        color: graph.color,
+        points: graph.points.clone(),
+        filled: vec![],
+    }
+}
+
+/// When the server started, which stands in for when data that is not read from a file changed.
+pub static STARTED: Lazy<SystemTime> = Lazy::new(SystemTime::now);
+
+/// Where a dataset is loaded from, and how it is described, as listed in the catalog.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Source {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub units: String,
+    #[serde(default)]
+    pub citation: String,
+    /// Within the data directory, without the `.tsv`.
+    pub path: String,
+    pub color: (u8, u8, u8),
+}
+
+/// Lists the datasets, within the data directory.
+const CATALOG: &str = "catalog.json";
+
+/// Every dataset in the catalog, whether or not it loads.
+pub fn catalog() -> Result<Vec<Source>> {
+    let path = data_dir().join(CATALOG);
+    let catalog = match std::fs::read(&path) {
+        Ok(catalog) => catalog,
+        Err(e) => embedded::data_file(Path::new(CATALOG))
+            .ok_or_else(|| Error::new(e.kind(), format!("{}: {}", path.display(), e)))?
+            .to_vec(),
+    };
+    serde_json::from_slice(&catalog).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Replaces the catalog in the data directory with `sources`.
+pub fn save_catalog(sources: &[Source]) -> Result<()> {
+    let mut catalog = serde_json::to_vec_pretty(sources)?;
+    catalog.push(b'\n');
+    std::fs::write(data_dir().join(CATALOG), catalog)
+}
+
+/// Every dataset that loaded. One that fails to load is left out rather than stopping the
+/// server, and counted in the metrics and in `FAILED`.
+pub static INDEX: Lazy<RwLock<HashMap<String, Arc<Dataset>>>> = Lazy::new(|| {
+    let mut failed = vec![];
+    let sources = catalog().unwrap_or_else(|e| {
+        error!("error reading the dataset catalog: {}", e);
+        failed.push(CATALOG.to_string());
+        vec![]
+    });
+    let index = sources
+        .iter()
+        .filter_map(|source| match load(source) {
+            Ok(dataset) => Some(dataset),
+            Err(e) => {
+                error!(
+                    "error loading dataset {} from {}: {}",
+                    source.name, source.path, e
+                );
+                metrics::DATASET_LOAD_FAILURES
+                    .with_label_values(&[&source.name])
+                    .inc();
+                failed.push(source.name.clone());
+                None
+            }
+        })
+        .map(|dataset| (dataset.graph.name.clone(), Arc::new(dataset)))
+        .collect();
+    // Set before `INDEX` is, so that anything that sees it loaded also sees what's missing
+    let _ = FAILED.set(failed);
+    RwLock::new(index)
 });
 
-pub static INDEX: Lazy<RwLock<HashMap<String, Graph>>> = Lazy::new(|| {
-    RwLock::new(
-        GRAPHS
-            .read()
-            .unwrap()
-            .iter()
-            .fold(HashMap::new(), |mut acc, graph| {
-                acc.insert(graph.name.to_string(), graph.clone());
-                acc
-            }),
-    )
+/// The datasets in the catalog that failed to load into `INDEX`, or the catalog itself if it
+/// couldn't be read.
+static FAILED: OnceCell<Vec<String>> = OnceCell::new();
+
+/// Whether `INDEX` has been loaded, without waiting for it.
+pub fn loaded() -> bool {
+    Lazy::get(&INDEX).is_some()
+}
+
+/// What failed to load into `INDEX`, which is nothing until it has been loaded.
+pub fn failed() -> &'static [String] {
+    FAILED.get().map_or(&[], Vec::as_slice)
+}
+
+pub fn load(source: &Source) -> Result<Dataset> {
+    let _span = info_span!("load dataset", dataset = source.name.as_str()).entered();
+    let start = Instant::now();
+    let (points, issues) = points_from_tsv(&source.path)?;
+    metrics::DATASET_LOAD_SECONDS
+        .with_label_values(&[&source.name])
+        .set(start.elapsed().as_secs_f64());
+    let graph = Graph {
+        name: source.name.clone(),
+        description: source.description.clone(),
+        units: source.units.clone(),
+        citation: source.citation.clone(),
+        points,
+        color: source.color,
+    };
+    Ok(Dataset::new(graph, issues, modified(&source.path)))
+}
+
+/// Loads the dataset called `name` in the catalog, for commands that run without the server.
+pub fn load_named(name: &str) -> Result<Dataset> {
+    let source = catalog()?
+        .into_iter()
+        .find(|source| source.name == name)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no dataset named {name}")))?;
+    load(&source)
+}
+
+/// A tiny series for working on the viewer, which is not listed in the index.
+static DEV: Lazy<Arc<Dataset>> = Lazy::new(|| {
+    let points = TimeSeries::new(vec![
+        (NaiveDate::from_ymd_opt(0, 1, 1).unwrap(), 0.0f32),
+        (NaiveDate::from_ymd_opt(0, 1, 2).unwrap(), 1.0f32),
+        (NaiveDate::from_ymd_opt(0, 1, 3).unwrap(), 2.0f32),
+    ]);
+    Arc::new(Dataset::new(
+        Graph {
+            name: "Dev".to_string(),
+            description: String::new(),
+            units: String::new(),
+            citation: String::new(),
+            points,
+            color: (0xEA, 0xFD, 0xCF),
+        },
+        vec![],
+        *STARTED,
+    ))
 });
 
+pub fn find(name: &str) -> Option<Arc<Dataset>> {
+    match INDEX.read().unwrap().get(name) {
+        Some(dataset) => Some(dataset.clone()),
+        None if name == "Dev" => Some(DEV.clone()),
+        None => None,
+    }
+}
+
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Sets where datasets are loaded from, which has to happen before anything looks in `INDEX`.
+pub fn set_data_dir(dir: PathBuf) {
+    if DATA_DIR.set(dir).is_err() {
+        warn!("the data directory was set after datasets were loaded");
+    }
+}
+
+fn data_dir() -> &'static Path {
+    DATA_DIR.get_or_init(|| PathBuf::from("data"))
+}
+
+/// Where the dataset at `path` in the catalog is.
+pub fn data_path(path: &str) -> PathBuf {
+    data_dir().join(format!("{path}.tsv"))
+}
+
+/// The file of the dataset at `path` in the catalog, or the copy built into the binary when it is
+/// not on disk.
+pub fn open_data(path: &str) -> Result<Box<dyn Read>> {
+    match File::open(data_path(path)) {
+        Ok(file) => Ok(Box::new(file)),
+        Err(e) => Ok(Box::new(
+            embedded::data_file(Path::new(&format!("{path}.tsv"))).ok_or(e)?,
+        )),
+    }
+}
+
+fn modified(path: &str) -> SystemTime {
+    std::fs::metadata(data_path(path))
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(*STARTED)
+}
+
 #[derive(Debug, Deserialize)]
 #[allow(non_snake_case)]
 struct Row {
@@ -44,8 +251,21 @@ struct Row {
     Value: f32,
 }
 
-fn points_from_tsv(path: &str) -> Result<Points> {
-    let file = File::open(format!("data/{path}.tsv"))?;
+/// The points in the dataset at `path`, along with any problems with them in the file.
+fn points_from_tsv(path: &str) -> Result<(TimeSeries, Vec<Issue>)> {
+    let points = read_points(open_data(path)?)?;
+    let issues = quality::check(&points);
+    for issue in &issues {
+        warn!(
+            "{}: point {} ({:?}): {}",
+            path, issue.index, issue.kind, issue.message
+        );
+    }
+    Ok((TimeSeries::new(points), issues))
+}
+
+/// The points in a TSV file with `Date` and `Value` columns, in the order they are in the file.
+pub fn read_points(file: impl Read) -> Result<Vec<Point>> {
     let mut rdr = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(file);
     let mut points = Vec::new();
     for result in rdr.deserialize() {
@@ -0,0 +1,143 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Result,
+};
+use clap::ValueEnum;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::{field::Empty, info, info_span, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::{graphs, telemetry};
+
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+/// Logs to stderr as `format`, at the levels set by `RUST_LOG` (info by default), and exports
+/// spans through `provider` if there is one.
+pub fn init(format: LogFormat, provider: Option<&SdkTracerProvider>) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let fmt = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+    let fmt = match format {
+        LogFormat::Text => fmt.boxed(),
+        LogFormat::Json => fmt.json().with_span_list(false).boxed(),
+    };
+    let otel = provider
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("nchoputa")));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt)
+        .with(otel)
+        .init();
+}
+
+/// The ID a request is logged under, as given by the client or proxy in `X-Request-Id` or else
+/// made up.
+#[derive(Clone, Debug)]
+struct RequestId(String);
+
+impl RequestId {
+    fn from_request(req: &ServiceRequest) -> Self {
+        let given = req
+            .headers()
+            .get(REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= 128);
+        match given {
+            Some(id) => RequestId(id.to_string()),
+            None => RequestId::generate(),
+        }
+    }
+
+    fn generate() -> Self {
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(format!("{:?}", *graphs::STARTED).as_bytes());
+        hasher.update(&std::process::id().to_le_bytes());
+        hasher.update(&COUNT.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+        RequestId(hasher.finalize().to_hex()[..16].to_string())
+    }
+}
+
+/// Middleware wrapping each request in a span, which ends with a line giving the route, dataset,
+/// status, size and duration. The request ID is echoed back in `X-Request-Id`.
+pub async fn trace_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>> {
+    let request_id = RequestId::from_request(&req);
+    let span = info_span!(
+        "request",
+        request_id = %request_id.0,
+        method = %req.method(),
+        path = %req.path(),
+        route = Empty,
+        dataset = Empty,
+    );
+    // Only fails when there is no exporter to carry the trace on
+    let _ = span.set_parent(telemetry::parent_context(req.headers()));
+
+    let start = Instant::now();
+    let mut res = next.call(req).instrument(span.clone()).await?;
+
+    let request = res.request();
+    if let Some(route) = request.match_name() {
+        span.record("route", route);
+    }
+    if let Some(dataset) = request.match_info().get("name") {
+        span.record("dataset", dataset);
+    }
+    let size = match res.response().body().size() {
+        BodySize::Sized(size) => Some(size),
+        BodySize::None => Some(0),
+        BodySize::Stream => None,
+    };
+    let status = res.status().as_u16();
+    span.in_scope(|| {
+        info!(
+            status,
+            size,
+            duration_ms = start.elapsed().as_secs_f64() * 1000.0,
+            "finished"
+        )
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id.0) {
+        res.headers_mut().insert(REQUEST_ID, value);
+    }
+    Ok(res)
+}
+
+#[actix_web::test]
+async fn test_request_id() {
+    use actix_web::{middleware::from_fn, test, web, App, HttpResponse};
+
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(trace_request))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().insert_header((REQUEST_ID, "abc123"));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.headers().get(REQUEST_ID).unwrap(), "abc123");
+
+    let res = test::call_service(&app, test::TestRequest::get().to_request()).await;
+    let generated = res.headers().get(REQUEST_ID).unwrap().to_str().unwrap();
+    assert_eq!(generated.len(), 16);
+}
@@ -1,100 +1,1541 @@
-use actix_files as fs;
 use actix_web::{
-    error, get, http::header, middleware, web, App, HttpResponse, HttpServer, Responder, Result,
+    error, get, http::header, middleware, web, App, HttpRequest, HttpResponse, HttpServer,
+    Responder, Result,
 };
+use std::ops::Bound;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
 use chrono::NaiveDate;
-use clap::Parser;
-use postcard::to_allocvec;
+use clap::{Parser, Subcommand};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
 
-use shared::response::{GraphData, GraphList, GraphSummary};
+use analysis::{
+    changepoint, compare, decimal_year, fill, forecast, linear_fit, resample, spectral, stl,
+};
+use assets::StaticDir;
+use caching::CachePolicies;
+use caching::Encoded;
+use encoding::{csv_response, encode, encode_body, encode_list, Format, ImageFormat};
+use graphs::Dataset;
+use plot::chart::Chart;
+use shared::response::{
+    ChangePoints, Comparison, Decomposition, Forecast, GraphData, GraphList, GraphSummary,
+    LagCorrelation, Periodogram, QualityReport, Table, TableRow,
+};
+use shared::series::TimeSeries;
 use tracing::{error, info};
+use utoipa::IntoParams;
 
+mod analysis;
+mod assets;
+mod caching;
+mod cli;
+mod embedded;
+mod encoding;
+mod explore;
 mod graphs;
+mod logging;
+mod metrics;
+mod openapi;
+mod plot;
+mod telemetry;
+mod tls;
 
 #[get("/favicon.ico")]
-async fn favicon() -> Result<fs::NamedFile> {
-    Ok(fs::NamedFile::open("static/favicon.ico")?)
+async fn favicon(req: HttpRequest, dir: web::Data<StaticDir>) -> Result<impl Responder> {
+    assets::serve(&req, &dir, "favicon.ico")
 }
 
-#[get("/api/graphs")]
-async fn list_graphs() -> impl Responder {
-    let list: Vec<GraphSummary> = graphs::INDEX
-        .read()
-        .unwrap().values().map(|graph| GraphSummary {
+#[get("/healthz")]
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().body("ok")
+}
+
+/// Ready once the datasets have been loaded, which happens in the background at startup, as long
+/// as none of them failed to.
+#[get("/readyz")]
+async fn readyz() -> HttpResponse {
+    if !graphs::loaded() {
+        return HttpResponse::ServiceUnavailable().body("loading datasets");
+    }
+    match graphs::failed() {
+        [] => HttpResponse::Ok().body("ok"),
+        failed => {
+            HttpResponse::ServiceUnavailable().body(format!("failed to load {}", failed.join(", ")))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct FormatQuery {
+    #[serde(default)]
+    #[param(inline)]
+    format: Format,
+}
+
+/// Every dataset, and where to fetch each from.
+#[utoipa::path(
+    tag = "graphs",
+    params(FormatQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (GraphList = "application/octet-stream"),
+                (GraphList = "application/json"),
+            )
+        ),
+        (status = 406, description = "The index is not available as CSV"),
+    ),
+)]
+#[get("/api/v1/graphs")]
+async fn list_graphs(
+    req: HttpRequest,
+    query: web::Query<FormatQuery>,
+    policies: web::Data<CachePolicies>,
+) -> Result<HttpResponse> {
+    let index = graphs::INDEX.read().unwrap();
+    let mut list: Vec<GraphSummary> = index
+        .values()
+        .map(|dataset| &dataset.graph)
+        .map(|graph| GraphSummary {
             name: graph.name.to_string(),
-            uri: format!("/api/graphs/{}", graph.name),
+            uri: format!("/api/v1/graphs/{}", graph.name),
             description: graph.description.to_string(),
             color: graph.color,
         })
         .collect();
-    to_allocvec(&GraphList { graphs: list }).map_err(|e| {
-        error!("error encoding graph index: {}", e);
-        error::ErrorInternalServerError("error encoding graph index")
-    })
+    // In a fixed order, so that the ETag stays the same across restarts
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+    let modified = index
+        .values()
+        .map(|dataset| dataset.modified)
+        .max()
+        .unwrap_or(*graphs::STARTED);
+
+    let (content_type, body) =
+        encode_body(query.format, &GraphList { graphs: list }, "graph index")?;
+    Ok(caching::respond(
+        &req,
+        policies.get("list_graphs"),
+        modified,
+        content_type,
+        &Encoded::new(body),
+    ))
 }
 
 //
 // EAFDCF
 // 8E8358
 //
-#[get("/api/graphs/{name}")]
-async fn show_graph(name: web::Path<String>) -> Result<impl Responder> {
-    let graph = match graphs::INDEX.read().unwrap().get(name.as_str()) {
-        Some(graph) => GraphData {
-            name: graph.name.to_string(),
-            color: graph.color,
-            points: graph.points.clone(),
-        },
-        None if name.as_str() == "Dev" => {
-            let points = vec![
-                (NaiveDate::from_ymd_opt(0, 1, 1).unwrap(), 0.0f32),
-                (NaiveDate::from_ymd_opt(0, 1, 2).unwrap(), 1.0f32),
-                (NaiveDate::from_ymd_opt(0, 1, 3).unwrap(), 2.0f32),
-            ];
-            GraphData {
-                name: name.to_string(),
-                color: (0xEA, 0xFD, 0xCF),
-                points,
-            }
+fn find_graph(name: &str) -> Result<Arc<Dataset>> {
+    graphs::find(name).ok_or_else(|| error::ErrorNotFound(format!("no graph with name {name}")))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BatchQuery {
+    /// Comma separated graph names.
+    names: String,
+    #[serde(default)]
+    #[param(inline)]
+    format: Format,
+}
+
+/// Several graphs in one response, in the order they were asked for.
+#[utoipa::path(
+    tag = "graphs",
+    params(BatchQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (Vec<GraphData> = "application/octet-stream"),
+                (Vec<GraphData> = "application/json"),
+            )
+        ),
+        (status = 404, description = "No graph has that name"),
+        (status = 406, description = "Graphs are not available as CSV"),
+    ),
+)]
+#[get("/api/v1/graphs/batch")]
+async fn batch_graphs(
+    req: HttpRequest,
+    query: web::Query<BatchQuery>,
+    policies: web::Data<CachePolicies>,
+) -> Result<HttpResponse> {
+    let datasets = query
+        .names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(find_graph)
+        .collect::<Result<Vec<Arc<Dataset>>>>()?;
+    let modified = datasets
+        .iter()
+        .map(|dataset| dataset.modified)
+        .max()
+        .unwrap_or(*graphs::STARTED);
+
+    let items: Vec<&[u8]> = datasets
+        .iter()
+        .filter_map(|dataset| dataset.encoded(query.format))
+        .map(|encoded| encoded.body.as_ref())
+        .collect();
+    let body = encode_list(query.format, &items, "graphs")?;
+    let response = caching::respond(
+        &req,
+        policies.get("batch_graphs"),
+        modified,
+        query.format.content_type(),
+        &Encoded::new(body),
+    );
+    for (dataset, item) in datasets.iter().zip(&items) {
+        metrics::served(&dataset.graph.name, &response, item.len());
+    }
+    Ok(response)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GraphQuery {
+    #[param(inline)]
+    fill: Option<fill::Method>,
+    /// The longest gap, in missing points, that will be filled.
+    max_gap: Option<usize>,
+    #[serde(default)]
+    #[param(inline)]
+    format: Format,
+}
+
+/// A graph's points, optionally with the gaps in them filled.
+#[utoipa::path(
+    tag = "graphs",
+    params(GraphQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (GraphData = "application/octet-stream"),
+                (GraphData = "application/json"),
+            )
+        ),
+        (status = 404, description = "No graph has that name"),
+        (status = 406, description = "Graphs are not available as CSV"),
+    ),
+)]
+#[get("/api/v1/graphs/{name}")]
+async fn show_graph(
+    req: HttpRequest,
+    name: web::Path<String>,
+    query: web::Query<GraphQuery>,
+    policies: web::Data<CachePolicies>,
+) -> Result<HttpResponse> {
+    let dataset = find_graph(&name)?;
+    let policy = policies.get("show_graph");
+
+    let Some(method) = query.fill else {
+        let encoded = dataset
+            .encoded(query.format)
+            .ok_or_else(|| error::ErrorNotAcceptable("graphs are not available as CSV"))?;
+        let response = caching::respond(
+            &req,
+            policy,
+            dataset.modified,
+            query.format.content_type(),
+            encoded,
+        );
+        metrics::served(&dataset.graph.name, &response, encoded.body.len());
+        return Ok(response);
+    };
+
+    let mut graph = dataset.data();
+    let (points, filled) = fill::fill_gaps(&graph.points, method, query.max_gap.unwrap_or(3));
+    graph.points = TimeSeries::new(points);
+    graph.filled = filled;
+
+    let (content_type, body) = encode_body(query.format, &graph, "graph")?;
+    let length = body.len();
+    let response = caching::respond(
+        &req,
+        policy,
+        dataset.modified,
+        content_type,
+        &Encoded::new(body),
+    );
+    metrics::served(&dataset.graph.name, &response, length);
+    Ok(response)
+}
+
+/// The sizes charts can be drawn at, in pixels along either side.
+const CHART_SIZES: std::ops::RangeInclusive<u32> = 100..=4000;
+/// The most pixels a chart can have in all, which bounds the memory drawing a PNG takes.
+const CHART_PIXELS: u32 = 4_000_000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ChartQuery {
+    /// Comma separated graphs to draw along with this one.
+    graphs: Option<String>,
+    /// In pixels, 1200 by default.
+    width: Option<u32>,
+    /// In pixels, 675 by default.
+    height: Option<u32>,
+    /// The first date shown, the start of the data by default.
+    from: Option<NaiveDate>,
+    /// The last date shown, the end of the data by default.
+    to: Option<NaiveDate>,
+}
+
+/// A chart of a graph, and any others asked for, as an image for places the viewer can't run.
+#[utoipa::path(
+    tag = "graphs",
+    params(
+        ("name" = String, Path, description = "The graph to draw"),
+        ("format" = ImageFormat, Path, description = "The extension to draw it as"),
+        ChartQuery,
+    ),
+    responses(
+        (
+            status = 200,
+            content(
+                (String = "image/svg+xml"),
+                (Vec<u8> = "image/png"),
+            )
+        ),
+        (status = 400, description = "The size or dates do not make sense, or leave nothing to draw"),
+        (status = 404, description = "No graph has that name"),
+    ),
+)]
+#[get("/api/v1/graphs/{name}.{format:svg|png}")]
+async fn show_chart(
+    req: HttpRequest,
+    path: web::Path<(String, ImageFormat)>,
+    query: web::Query<ChartQuery>,
+    policies: web::Data<CachePolicies>,
+) -> Result<HttpResponse> {
+    let (name, format) = path.into_inner();
+    let mut datasets = vec![find_graph(&name)?];
+    for other in query.graphs.iter().flat_map(|graphs| graphs.split(',')) {
+        let other = other.trim();
+        if !other.is_empty() && datasets.iter().all(|dataset| dataset.graph.name != other) {
+            datasets.push(find_graph(other)?);
+        }
+    }
+
+    let size = (query.width.unwrap_or(1200), query.height.unwrap_or(675));
+    if !CHART_SIZES.contains(&size.0) || !CHART_SIZES.contains(&size.1) {
+        return Err(error::ErrorBadRequest(format!(
+            "width and height must be from {} to {} pixels",
+            CHART_SIZES.start(),
+            CHART_SIZES.end()
+        )));
+    }
+    if size.0 * size.1 > CHART_PIXELS {
+        return Err(error::ErrorBadRequest(format!(
+            "charts can have at most {CHART_PIXELS} pixels"
+        )));
+    }
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(error::ErrorBadRequest("from is after to"));
         }
-        _ => return Err(error::ErrorNotFound(format!("no graph with name {name}"))),
+    }
+
+    let range = (
+        query.from.map_or(Bound::Unbounded, Bound::Included),
+        query.to.map_or(Bound::Unbounded, Bound::Included),
+    );
+    let dates = datasets.iter().flat_map(|dataset| {
+        dataset
+            .graph
+            .points
+            .range(range)
+            .iter()
+            .map(|(date, _)| *date)
+    });
+    let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) else {
+        return Err(error::ErrorBadRequest("no points fall between those dates"));
+    };
+    let (from, to) = (query.from.unwrap_or(first), query.to.unwrap_or(last));
+
+    // The chart only depends on the datasets and what was asked for, so a client that already
+    // has it is answered without drawing it again
+    let policy = policies.get("show_chart");
+    let modified = datasets
+        .iter()
+        .map(|dataset| dataset.modified)
+        .max()
+        .unwrap_or(*graphs::STARTED);
+    let mut inputs = format!("{} {}", env!("CARGO_PKG_VERSION"), req.uri());
+    for dataset in &datasets {
+        inputs.push(' ');
+        inputs.push_str(dataset.postcard.etag.tag());
+    }
+    let etag = caching::etag(inputs.as_bytes());
+    if caching::unchanged(&req, &etag, modified) {
+        let encoded = Encoded {
+            body: Default::default(),
+            etag,
+        };
+        return Ok(caching::respond(
+            &req,
+            policy,
+            modified,
+            format.content_type(),
+            &encoded,
+        ));
+    }
+
+    // Drawing takes long enough to hold up other requests on the worker
+    let drawn = datasets.clone();
+    let body = web::block(move || draw_chart(&drawn, from, to, format, size))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|body| body)
+        .map_err(|e| {
+            error!("error drawing chart of {}: {}", name, e);
+            error::ErrorInternalServerError("error drawing chart")
+        })?;
+
+    let length = body.len();
+    let encoded = Encoded {
+        body: body.into(),
+        etag,
+    };
+    let response = caching::respond(&req, policy, modified, format.content_type(), &encoded);
+    metrics::served(&name, &response, length);
+    Ok(response)
+}
+
+/// Draws `datasets` from `from` to `to`, labelled with their units and credited to their
+/// citations.
+fn draw_chart(
+    datasets: &[Arc<Dataset>],
+    from: NaiveDate,
+    to: NaiveDate,
+    format: ImageFormat,
+    size: (u32, u32),
+) -> std::result::Result<Vec<u8>, String> {
+    let series: Vec<plot::Series> = datasets
+        .iter()
+        .map(|dataset| plot::Series {
+            name: &dataset.graph.name,
+            color: dataset.graph.color,
+            points: dataset.graph.points.range(from..=to),
+        })
+        .collect();
+    let mut units: Vec<&str> = vec![];
+    for dataset in datasets {
+        if !dataset.graph.units.is_empty() && !units.contains(&dataset.graph.units.as_str()) {
+            units.push(&dataset.graph.units);
+        }
+    }
+    let chart = Chart {
+        series: &series,
+        from,
+        to,
+        units: units.join(", "),
+        citations: datasets
+            .iter()
+            .filter(|dataset| !dataset.graph.citation.is_empty())
+            .map(|dataset| format!("{}: {}", dataset.graph.name, dataset.graph.citation))
+            .collect(),
+    };
+    match format {
+        ImageFormat::Svg => chart.svg(size).map(String::into_bytes),
+        ImageFormat::Png => chart.png(size),
+    }
+}
+
+/// Points in a graph that look wrong, as they were in the file it was loaded from.
+#[utoipa::path(
+    tag = "analysis",
+    params(FormatQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (QualityReport = "application/octet-stream"),
+                (QualityReport = "application/json"),
+            )
+        ),
+        (status = 404, description = "No graph has that name"),
+        (status = 406, description = "Quality reports are not available as CSV"),
+    ),
+)]
+#[get("/api/v1/graphs/{name}/quality")]
+async fn show_quality(
+    name: web::Path<String>,
+    query: web::Query<FormatQuery>,
+) -> Result<HttpResponse> {
+    let dataset = find_graph(&name)?;
+
+    // Checked when the dataset was loaded, since it has been sorted and deduplicated since
+    let report = QualityReport {
+        issues: dataset.issues.clone(),
+        name: dataset.graph.name.to_string(),
+    };
+
+    encode(query.format, &report, "quality report")
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ChangePointQuery {
+    #[serde(default)]
+    #[param(inline)]
+    method: changepoint::Method,
+    #[serde(default)]
+    #[param(inline)]
+    model: changepoint::Model,
+    /// Cost of adding a change point, higher finds fewer. Defaults to one based on the noise.
+    penalty: Option<f64>,
+    /// The fewest points in a segment, 4 by default. At most half the points in the graph.
+    min_size: Option<usize>,
+    #[serde(default)]
+    #[param(inline)]
+    format: Format,
+}
+
+/// Where the behaviour of a graph changes, and the segments in between.
+#[utoipa::path(
+    tag = "analysis",
+    params(ChangePointQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (ChangePoints = "application/octet-stream"),
+                (ChangePoints = "application/json"),
+            )
+        ),
+        (status = 400, description = "min_size is zero or more than half the points in the graph"),
+        (status = 404, description = "No graph has that name"),
+        (status = 406, description = "Change points are not available as CSV"),
+    ),
+)]
+#[get("/api/v1/graphs/{name}/changepoints")]
+async fn show_changepoints(
+    name: web::Path<String>,
+    query: web::Query<ChangePointQuery>,
+) -> Result<HttpResponse> {
+    let dataset = find_graph(&name)?;
+    let graph = &dataset.graph;
+    if let Some(min_size) = query.min_size {
+        if min_size == 0 || min_size > graph.points.len() / 2 {
+            return Err(error::ErrorBadRequest(format!(
+                "min_size must be from 1 to half the {} points in {}",
+                graph.points.len(),
+                graph.name
+            )));
+        }
+    }
+
+    let breakpoints = changepoint::detect(
+        &graph.points,
+        query.method,
+        query.model,
+        query.penalty,
+        query.min_size.unwrap_or(4),
+    );
+    let changepoints = ChangePoints {
+        name: graph.name.to_string(),
+        breakpoints: breakpoints.iter().map(|&i| graph.points[i].0).collect(),
+        segments: changepoint::segments(&graph.points, &breakpoints),
+    };
+
+    encode(query.format, &changepoints, "change points")
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DecompositionQuery {
+    /// Length of the seasonal cycle in points, detected when not given.
+    period: Option<usize>,
+    #[serde(default)]
+    #[param(inline)]
+    format: Format,
+}
+
+/// A graph split into trend, seasonal and residual components.
+#[utoipa::path(
+    tag = "analysis",
+    params(DecompositionQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (Decomposition = "application/octet-stream"),
+                (Decomposition = "application/json"),
+            )
+        ),
+        (status = 400, description = "The query does not make sense for the graph"),
+        (status = 404, description = "No graph has that name"),
+        (status = 406, description = "Decompositions are not available as CSV"),
+    ),
+)]
+#[get("/api/v1/graphs/{name}/decomposition")]
+async fn show_decomposition(
+    name: web::Path<String>,
+    query: web::Query<DecompositionQuery>,
+) -> Result<HttpResponse> {
+    let dataset = find_graph(&name)?;
+    let graph = &dataset.graph;
+
+    let period = query
+        .period
+        .or_else(|| stl::detect_period(&graph.points))
+        .ok_or_else(|| error::ErrorBadRequest("could not detect a seasonal period, set one"))?;
+    if period < 2 || period > graph.points.len() / 2 {
+        return Err(error::ErrorBadRequest(format!(
+            "a period of {period} needs at least two whole cycles of data"
+        )));
+    }
+
+    let ys: Vec<f64> = graph.points.iter().map(|(_, y)| *y as f64).collect();
+    let stl::Decomposition {
+        trend,
+        seasonal,
+        residual,
+    } = stl::decompose(&ys, period);
+    let component = |label: &str, values: Vec<f64>| GraphData {
+        name: format!("{} {}", graph.name, label),
+        color: graph.color,
+        points: graph
+            .points
+            .iter()
+            .zip(values)
+            .map(|((date, _), value)| (*date, value as f32))
+            .collect(),
+        filled: vec![],
+    };
+    let decomposition = Decomposition {
+        name: graph.name.to_string(),
+        period: period as u32,
+        trend: component("trend", trend),
+        seasonal: component("seasonal", seasonal),
+        residual: component("residual", residual),
+    };
+
+    encode(query.format, &decomposition, "decomposition")
+}
+
+fn default_detrend() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PeriodogramQuery {
+    /// Shortest period to look at, in years.
+    min_period: Option<f64>,
+    /// Longest period to look at, in years.
+    max_period: Option<f64>,
+    /// How many periods between the two to work out the power of, at least 2 and at most 10000.
+    samples: Option<usize>,
+    /// Whether to remove a linear trend first.
+    #[serde(default = "default_detrend")]
+    detrend: bool,
+    #[serde(default)]
+    #[param(inline)]
+    format: Format,
+}
+
+/// The periods, in years, that a graph repeats over.
+#[utoipa::path(
+    tag = "analysis",
+    params(PeriodogramQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (Periodogram = "application/octet-stream"),
+                (Periodogram = "application/json"),
+            )
+        ),
+        (status = 400, description = "The query does not make sense for the graph"),
+        (status = 404, description = "No graph has that name"),
+        (status = 406, description = "Periodograms are not available as CSV"),
+    ),
+)]
+#[get("/api/v1/graphs/{name}/periodogram")]
+async fn show_periodogram(
+    name: web::Path<String>,
+    query: web::Query<PeriodogramQuery>,
+) -> Result<HttpResponse> {
+    let dataset = find_graph(&name)?;
+    let graph = &dataset.graph;
+    if graph.points.len() < 3 {
+        return Err(error::ErrorBadRequest(
+            "not enough points for a periodogram",
+        ));
+    }
+
+    let ts: Vec<f64> = graph
+        .points
+        .iter()
+        .map(|(date, _)| decimal_year(date))
+        .collect();
+    let mut ys: Vec<f64> = graph.points.iter().map(|(_, y)| *y as f64).collect();
+    if query.detrend {
+        let (intercept, slope) = linear_fit(&ts, &ys);
+        for (t, y) in ts.iter().zip(ys.iter_mut()) {
+            *y -= intercept + slope * t;
+        }
+    }
+
+    // By default cover everything from the Nyquist period to the length of the record
+    let interval = analysis::sampling_interval(&graph.points).unwrap_or(1.0) / 365.25;
+    let min_period = query.min_period.unwrap_or(2.0 * interval);
+    let max_period = query.max_period.unwrap_or(ts[ts.len() - 1] - ts[0]);
+    if !min_period.is_finite() || !max_period.is_finite() {
+        return Err(error::ErrorBadRequest("the period range must be finite"));
+    }
+    if min_period <= 0.0 || max_period <= min_period {
+        return Err(error::ErrorBadRequest("the period range is empty"));
+    }
+
+    let samples = query.samples.unwrap_or(500).min(10_000);
+    if samples < 2 {
+        return Err(error::ErrorBadRequest("samples must be at least 2"));
+    }
+    let periods = spectral::log_spaced(min_period, max_period, samples);
+    let power = spectral::lomb_scargle(&ts, &ys, &periods);
+    let periodogram = Periodogram {
+        name: graph.name.to_string(),
+        color: graph.color,
+        points: periods
+            .iter()
+            .zip(power)
+            .map(|(period, power)| (*period as f32, power as f32))
+            .collect(),
+    };
+
+    encode(query.format, &periodogram, "periodogram")
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CompareQuery {
+    a: String,
+    b: String,
+    /// Furthest, in samples, to shift `b` by for the cross-correlation. Must be less than the
+    /// number of samples where the graphs overlap.
+    max_lag: Option<usize>,
+    #[serde(default)]
+    #[param(inline)]
+    format: Format,
+}
+
+/// How closely two graphs agree where they overlap.
+#[utoipa::path(
+    tag = "analysis",
+    params(CompareQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (Comparison = "application/octet-stream"),
+                (Comparison = "application/json"),
+            )
+        ),
+        (status = 400, description = "The graphs do not overlap enough to compare, or max_lag is longer than the overlap"),
+        (status = 404, description = "No graph has that name"),
+        (status = 406, description = "Comparisons are not available as CSV"),
+    ),
+)]
+#[get("/api/v1/compare")]
+async fn compare_graphs(query: web::Query<CompareQuery>) -> Result<HttpResponse> {
+    let a = &find_graph(&query.a)?.graph;
+    let b = &find_graph(&query.b)?.graph;
+
+    let aligned = compare::align(&a.points, &b.points);
+    if aligned.len() < 3 {
+        return Err(error::ErrorBadRequest(format!(
+            "{} and {} do not overlap enough to compare",
+            a.name, b.name
+        )));
+    }
+    let max_lag = query.max_lag.unwrap_or(20);
+    if query.max_lag.is_some() && max_lag >= aligned.len() {
+        return Err(error::ErrorBadRequest(format!(
+            "max_lag must be less than the {} samples where {} and {} overlap",
+            aligned.len(),
+            a.name,
+            b.name
+        )));
+    }
+    let dates: Vec<NaiveDate> = aligned.iter().map(|(date, _, _)| *date).collect();
+    let xs: Vec<f64> = aligned.iter().map(|(_, x, _)| *x).collect();
+    let ys: Vec<f64> = aligned.iter().map(|(_, _, y)| *y).collect();
+
+    let n = aligned.len() as f64;
+    let differences = xs.iter().zip(&ys).map(|(x, y)| y - x);
+    let bias = differences.clone().sum::<f64>() / n;
+    let rmse = (differences.map(|d| d * d).sum::<f64>() / n).sqrt();
+
+    let interval = (dates[dates.len() - 1] - dates[0]).num_days() as f64 / (n - 1.0);
+    let cross_correlation = compare::cross_correlation(&xs, &ys, max_lag)
+        .into_iter()
+        .map(|(lag, correlation)| LagCorrelation {
+            lag,
+            days: (lag as f64 * interval).round() as i32,
+            correlation: correlation as f32,
+        })
+        .collect();
+
+    let comparison = Comparison {
+        a: a.name.to_string(),
+        b: b.name.to_string(),
+        start: dates[0],
+        end: dates[dates.len() - 1],
+        samples: aligned.len() as u32,
+        pearson: compare::pearson(&xs, &ys) as f32,
+        spearman: compare::spearman(&xs, &ys) as f32,
+        rmse: rmse as f32,
+        bias: bias as f32,
+        cross_correlation,
+    };
+
+    encode(query.format, &comparison, "comparison")
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ForecastQuery {
+    #[serde(default)]
+    #[param(inline)]
+    method: forecast::Method,
+    /// How far past the last observation to project.
+    years: Option<f64>,
+    /// Confidence level of the bounds.
+    level: Option<f64>,
+    /// Only fit the observations from this date onwards.
+    since: Option<NaiveDate>,
+    #[serde(default)]
+    #[param(inline)]
+    format: Format,
+}
+
+/// A graph projected past its last observation.
+#[utoipa::path(
+    tag = "analysis",
+    params(ForecastQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (Forecast = "application/octet-stream"),
+                (Forecast = "application/json"),
+            )
+        ),
+        (status = 400, description = "The query does not make sense for the graph"),
+        (status = 404, description = "No graph has that name"),
+        (status = 406, description = "Forecasts are not available as CSV"),
+    ),
+)]
+#[get("/api/v1/graphs/{name}/forecast")]
+async fn show_forecast(
+    name: web::Path<String>,
+    query: web::Query<ForecastQuery>,
+) -> Result<HttpResponse> {
+    let dataset = find_graph(&name)?;
+    let graph = &dataset.graph;
+
+    let years = query.years.unwrap_or(10.0);
+    let level = query.level.unwrap_or(0.95);
+    if !(0.0..=200.0).contains(&years) || !(0.0..1.0).contains(&level) {
+        return Err(error::ErrorBadRequest(
+            "years must be within 0 to 200 and level within 0 to 1",
+        ));
+    }
+
+    let observed = match query.since {
+        Some(since) => graph.points.range(since..),
+        None => graph.points.points(),
+    };
+    let points = forecast::forecast(observed, query.method, years, level)
+        .ok_or_else(|| error::ErrorBadRequest("not enough observations to forecast from"))?;
+
+    let forecast = Forecast {
+        name: graph.name.to_string(),
+        color: graph.color,
+        method: format!("{:?}", query.method).to_lowercase(),
+        observed_until: observed[observed.len() - 1].0,
+        level: level as f32,
+        points,
     };
 
-    to_allocvec(&graph).map_err(|e| {
-        error!("error encoding dataset {}: {}", name, e);
-        error::ErrorInternalServerError("error encoding dataset")
+    encode(query.format, &forecast, "forecast")
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TableQuery {
+    /// Comma separated graph names.
+    graphs: String,
+    #[serde(default)]
+    #[param(inline)]
+    grid: resample::Grid,
+    #[serde(default)]
+    #[param(inline)]
+    format: Format,
+}
+
+/// Several graphs resampled onto the same dates.
+#[utoipa::path(
+    tag = "analysis",
+    params(TableQuery),
+    responses(
+        (
+            status = 200,
+            content(
+                (Table = "application/octet-stream"),
+                (Table = "application/json"),
+                (Table = "text/csv"),
+            )
+        ),
+        (status = 400, description = "No graphs were asked for"),
+        (status = 404, description = "No graph has that name"),
+    ),
+)]
+#[get("/api/v1/table")]
+async fn show_table(query: web::Query<TableQuery>) -> Result<HttpResponse> {
+    let graphs = query
+        .graphs
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(find_graph)
+        .collect::<Result<Vec<Arc<Dataset>>>>()?;
+    if graphs.is_empty() {
+        return Err(error::ErrorBadRequest("no graphs requested"));
+    }
+
+    let series: Vec<&TimeSeries> = graphs.iter().map(|dataset| &dataset.graph.points).collect();
+    let table = Table {
+        columns: graphs
+            .iter()
+            .map(|dataset| dataset.graph.name.to_string())
+            .collect(),
+        rows: resample::resample(&series, query.grid)
+            .into_iter()
+            .map(|(date, values)| TableRow { date, values })
+            .collect(),
+    };
+
+    if query.format != Format::Csv {
+        return encode(query.format, &table, "table");
+    }
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let encoded = writer
+        .write_record(std::iter::once("Date").chain(table.columns.iter().map(String::as_str)))
+        .and_then(|_| {
+            table.rows.iter().try_for_each(|row| {
+                writer.write_record(
+                    std::iter::once(row.date.to_string()).chain(
+                        row.values
+                            .iter()
+                            .map(|value| value.map(|v| v.to_string()).unwrap_or_default()),
+                    ),
+                )
+            })
+        })
+        .map_err(|e| e.to_string())
+        .and_then(|_| writer.into_inner().map_err(|e| e.to_string()));
+    encoded.map(csv_response).map_err(|e| {
+        error!("error encoding table as CSV: {}", e);
+        error::ErrorInternalServerError("error encoding table")
     })
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(short, long, default_value_t = 8999)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Directory the datasets are loaded from
+    #[arg(long, global = true, env = "NCHOPUTA_DATA_DIR", default_value = "data")]
+    data_dir: PathBuf,
+
+    #[arg(
+        long,
+        global = true,
+        env = "NCHOPUTA_LOG_FORMAT",
+        value_enum,
+        default_value_t
+    )]
+    log_format: logging::LogFormat,
+
+    /// OTLP/HTTP collector to export traces to, e.g. http://localhost:4318
+    #[arg(long, global = true, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+}
+
+/// How the server listens, and what it sends. Parsed on its own for when the server is started
+/// without `serve`, from the environment alone.
+#[derive(Parser, Debug)]
+struct ServeArgs {
+    /// Address to listen on
+    #[arg(short, long, env = "NCHOPUTA_ADDRESS", default_value = "0.0.0.0")]
+    address: String,
+
+    #[arg(short, long, env = "NCHOPUTA_PORT", default_value_t = 8999)]
     port: u16,
+
+    /// Number of worker threads
+    #[arg(short, long, env = "NCHOPUTA_WORKERS", default_value_t = 1)]
+    workers: usize,
+
+    /// Directory of the viewer and other files served under /s
+    #[arg(long, env = "NCHOPUTA_STATIC_DIR", default_value = "static")]
+    static_dir: PathBuf,
+
+    /// PEM certificate chain, to serve HTTPS rather than HTTP
+    #[arg(long, env = "NCHOPUTA_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, env = "NCHOPUTA_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Overrides the Cache-Control header a route sends, e.g. show_graph="max-age=60". Separated
+    /// by semicolons in the environment variable.
+    #[arg(
+        long = "cache-control",
+        env = "NCHOPUTA_CACHE_CONTROL",
+        value_name = "ROUTE=POLICY",
+        value_delimiter = ';',
+        value_parser = caching::parse_policy
+    )]
+    cache_control: Vec<(String, String)>,
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    tracing_subscriber::fmt::init();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Starts the server, which is also what happens without a command
+    Serve(ServeArgs),
+    /// Parses every dataset in the catalog, or the given files, and prints what looks wrong in
+    /// them
+    Validate {
+        /// TSV files with Date and Value columns
+        files: Vec<PathBuf>,
+    },
+    /// Prints the catalog of datasets
+    List,
+    /// Writes a dataset to stdout
+    Export {
+        name: String,
+        #[arg(long, value_enum, default_value_t)]
+        format: cli::ExportFormat,
+    },
+    /// Draws datasets in the terminal
+    Plot {
+        #[arg(required = true)]
+        names: Vec<String>,
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// In characters [default: $COLUMNS or 100]
+        #[arg(long)]
+        width: Option<usize>,
+        /// In lines, including the axis and legend [default: $LINES or 30]
+        #[arg(long)]
+        height: Option<usize>,
+    },
+    /// Browses the datasets in a full screen terminal interface
+    Explore,
+    /// Copies a TSV file with Date and Value columns into the data directory, and adds it to the
+    /// catalog
+    Import {
+        file: PathBuf,
+        #[command(flatten)]
+        dataset: cli::NewDataset,
+    },
+    /// Writes .br and .gz copies of the static files, which are served to clients that accept them
+    Precompress {
+        #[arg(env = "NCHOPUTA_STATIC_DIR", default_value = "static")]
+        dir: PathBuf,
+    },
+}
 
+fn main() -> ExitCode {
     let args = Args::parse();
 
-    info!("Listening on http://localhost:{}/ ...", args.port);
-    HttpServer::new(|| {
+    // Set up outside of the server's runtime, which the exporter's HTTP client can't run in
+    let provider = match args
+        .otlp_endpoint
+        .as_deref()
+        .map(telemetry::provider)
+        .transpose()
+    {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("error setting up trace export: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    logging::init(args.log_format, provider.as_ref());
+    graphs::set_data_dir(args.data_dir.clone());
+
+    let result = match args.command {
+        Some(Command::Validate { ref files }) => cli::validate(files),
+        Some(Command::List) => cli::list(),
+        Some(Command::Export { ref name, format }) => cli::export(name, format),
+        Some(Command::Plot {
+            ref names,
+            from,
+            to,
+            width,
+            height,
+        }) => cli::plot(names, from, to, width, height),
+        Some(Command::Explore) => explore::run(),
+        Some(Command::Import {
+            ref file,
+            ref dataset,
+        }) => cli::import(file, dataset),
+        Some(Command::Precompress { ref dir }) => assets::precompress(dir),
+        Some(Command::Serve(serve_args)) => {
+            actix_web::rt::System::new().block_on(serve(serve_args))
+        }
+        None => actix_web::rt::System::new().block_on(serve(ServeArgs::parse_from(["nchoputa"]))),
+    };
+    if let Err(e) = &result {
+        error!("{}", e);
+    }
+
+    if let Some(provider) = provider {
+        if let Err(e) = provider.shutdown() {
+            error!("error flushing traces: {}", e);
+        }
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+/// Every route the server answers, which rely on the app data set up in `serve`.
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(favicon)
+        .service(healthz)
+        .service(readyz)
+        .service(metrics::show_metrics)
+        .service(assets::static_file)
+        .service(web::resource("/").route(web::get().to(|| async {
+            HttpResponse::Found()
+                .insert_header((header::LOCATION, "/s/index.html"))
+                .finish()
+        })))
+        .service(list_graphs)
+        .service(batch_graphs)
+        // Before show_graph, which would otherwise take the extension as part of the name. Names
+        // with other dots in them still go to show_graph.
+        .service(show_chart)
+        .service(show_graph)
+        .service(show_quality)
+        .service(show_changepoints)
+        .service(show_decomposition)
+        .service(show_periodogram)
+        .service(show_forecast)
+        .service(compare_graphs)
+        .service(show_table)
+        .service(openapi::show_openapi);
+}
+
+async fn serve(args: ServeArgs) -> std::io::Result<()> {
+    let policies = web::Data::new(CachePolicies::new(&args.cache_control));
+    let static_dir = web::Data::new(StaticDir(args.static_dir.clone()));
+    Lazy::force(&graphs::STARTED);
+    std::thread::spawn(|| Lazy::force(&graphs::INDEX));
+
+    let server = HttpServer::new(move || {
         App::new()
-            .wrap(middleware::Logger::default())
-            .service(favicon)
-            .service(fs::Files::new("/s", "static"))
-            .service(web::resource("/").route(web::get().to(|| async {
-                HttpResponse::Found()
-                    .insert_header((header::LOCATION, "/s/index.html"))
-                    .finish()
-            })))
-            .service(list_graphs)
-            .service(show_graph)
+            .app_data(policies.clone())
+            .app_data(static_dir.clone())
+            .wrap(middleware::from_fn(logging::trace_request))
+            .wrap(middleware::Compress::default())
+            .wrap(middleware::from_fn(metrics::record))
+            .configure(routes)
     })
-    .workers(1)
-    .bind(format!("0.0.0.0:{}", args.port))?
-    .run()
-    .await
+    .workers(args.workers);
+
+    let address = (args.address.as_str(), args.port);
+    let server = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => {
+            info!("Listening on https://{}:{}/ ...", args.address, args.port);
+            server.bind_rustls_0_23(address, tls::server_config(cert, key)?)?
+        }
+        _ => {
+            info!("Listening on http://{}:{}/ ...", args.address, args.port);
+            server.bind(address)?
+        }
+    };
+    server.run().await
+}
+
+/// Serves the API on a port of its own, from a thread of its own so that blocking clients can be
+/// tested against it too. Returns the server's base URL.
+#[cfg(test)]
+fn spawn_test_server() -> String {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        actix_web::rt::System::new().block_on(async move {
+            let policies = web::Data::new(CachePolicies::new(&[]));
+            let static_dir = web::Data::new(StaticDir(PathBuf::from("static")));
+            let server = HttpServer::new(move || {
+                App::new()
+                    .app_data(policies.clone())
+                    .app_data(static_dir.clone())
+                    .configure(routes)
+            })
+            .workers(1)
+            .bind(("127.0.0.1", 0))?;
+            sender.send(server.addrs()[0]).unwrap();
+            server.run().await
+        })
+    });
+    format!("http://{}", receiver.recv().unwrap())
+}
+
+/// Held by tests that add datasets to the index for a while, and by those that list it.
+#[cfg(test)]
+static INDEX_CHANGES: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(test)]
+fn lock_index() -> std::sync::MutexGuard<'static, ()> {
+    INDEX_CHANGES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[test]
+fn test_blocking_client() {
+    use shared::client::{blocking::Client, Error, GraphOptions};
+
+    let client = Client::new(&spawn_test_server()).unwrap();
+    let index = lock_index();
+    let list = client.list_graphs().unwrap();
+    drop(index);
+    let names: Vec<&str> = list
+        .graphs
+        .iter()
+        .map(|graph| graph.name.as_str())
+        .collect();
+    assert_eq!(names, ["CSIRO", "UHSLC"]);
+
+    let graph = client.graph("CSIRO", &GraphOptions::default()).unwrap();
+    assert_eq!(graph.name, "CSIRO");
+    assert!(!graph.points.is_empty() && graph.filled.is_empty());
+
+    let options = GraphOptions {
+        fill: Some(fill::Method::Linear),
+        max_gap: Some(12),
+    };
+    let observed = client.graph("UHSLC", &GraphOptions::default()).unwrap();
+    let filled = client.graph("UHSLC", &options).unwrap();
+    assert_eq!(
+        filled.points.len(),
+        observed.points.len() + filled.filled.len()
+    );
+
+    assert!(matches!(
+        client.graph("Nothing", &GraphOptions::default()),
+        Err(Error::Status { status, .. }) if status == 404
+    ));
+}
+
+#[actix_web::test]
+// The runtime is single threaded, and the lock is only ever waited on by other tests' threads
+#[allow(clippy::await_holding_lock)]
+async fn test_async_client() {
+    use shared::client::Client;
+
+    let client = Client::new(&spawn_test_server()).unwrap();
+    let batch = client.graphs(&["UHSLC", "CSIRO"]).await.unwrap();
+    let names: Vec<&str> = batch.iter().map(|graph| graph.name.as_str()).collect();
+    assert_eq!(names, ["UHSLC", "CSIRO"]);
+    let index = lock_index();
+    assert_eq!(client.list_graphs().await.unwrap().graphs.len(), 2);
+    drop(index);
+}
+
+#[actix_web::test]
+#[allow(clippy::await_holding_lock)]
+async fn test_show_quality() {
+    use actix_web::test;
+    use shared::response::{Graph, IssueKind};
+
+    // Out of order, and with 2000-03 twice
+    let tsv = "Date\tValue\n2000-01-15\t1.0\n2000-03-15\t3.0\n2000-02-15\t2.0\n\
+               2000-03-15\t3.0\n2000-04-15\t4.0\n";
+    let points = graphs::read_points(tsv.as_bytes()).unwrap();
+    let issues = analysis::quality::check(&points);
+    let graph = Graph {
+        name: "Unsorted".to_string(),
+        description: String::new(),
+        units: String::new(),
+        citation: String::new(),
+        points: TimeSeries::new(points),
+        color: (0xEA, 0xFD, 0xCF),
+    };
+    let dataset = Dataset::new(graph, issues, std::time::SystemTime::UNIX_EPOCH);
+
+    let index = lock_index();
+    graphs::INDEX
+        .write()
+        .unwrap()
+        .insert("Unsorted".to_string(), Arc::new(dataset));
+    let app = test::init_service(App::new().service(show_quality)).await;
+    let req = test::TestRequest::get()
+        .uri("/api/v1/graphs/Unsorted/quality?format=json")
+        .to_request();
+    let report: QualityReport = test::call_and_read_body_json(&app, req).await;
+    graphs::INDEX.write().unwrap().remove("Unsorted");
+    drop(index);
+
+    let kinds: Vec<(IssueKind, u32)> = report
+        .issues
+        .iter()
+        .map(|issue| (issue.kind, issue.index))
+        .collect();
+    assert!(kinds.contains(&(IssueKind::Unsorted, 2)), "{kinds:?}");
+    assert!(kinds.contains(&(IssueKind::Duplicate, 3)), "{kinds:?}");
+}
+
+#[actix_web::test]
+async fn test_compare_max_lag() {
+    use actix_web::{http::StatusCode, test};
+
+    let app = test::init_service(App::new().service(compare_graphs)).await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/api/v1/compare?a=CSIRO&b=UHSLC&max_lag=3")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let huge = "/api/v1/compare?a=CSIRO&b=UHSLC&max_lag=100000000000";
+    assert_eq!(
+        test::call_service(&app, get(huge)).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_web::test]
+async fn test_json_formats() {
+    use actix_web::{http::StatusCode, test};
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(CachePolicies::new(&[])))
+            .configure(routes),
+    )
+    .await;
+    for uri in [
+        "/api/v1/graphs",
+        "/api/v1/graphs/CSIRO/quality",
+        "/api/v1/graphs/CSIRO/changepoints",
+        "/api/v1/graphs/UHSLC/decomposition",
+        "/api/v1/graphs/CSIRO/periodogram",
+        "/api/v1/graphs/CSIRO/forecast",
+        "/api/v1/compare?a=CSIRO&b=UHSLC",
+    ] {
+        let separator = if uri.contains('?') { '&' } else { '?' };
+        let req = test::TestRequest::get()
+            .uri(&format!("{uri}{separator}format=json"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK, "{uri}");
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json",
+            "{uri}"
+        );
+        let body = test::read_body(res).await;
+        assert!(
+            serde_json::from_slice::<serde_json::Value>(&body).is_ok(),
+            "{uri}"
+        );
+
+        let req = test::TestRequest::get()
+            .uri(&format!("{uri}{separator}format=csv"))
+            .to_request();
+        let status = test::call_service(&app, req).await.status();
+        assert_eq!(status, StatusCode::NOT_ACCEPTABLE, "{uri}");
+    }
+}
+
+#[actix_web::test]
+async fn test_changepoints_min_size() {
+    use actix_web::{http::StatusCode, test};
+
+    let app = test::init_service(App::new().service(show_changepoints)).await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/api/v1/graphs/CSIRO/changepoints?min_size=8")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    for min_size in [
+        "0".to_string(),
+        "100000".to_string(),
+        (usize::MAX / 2 + 1).to_string(),
+    ] {
+        let uri = format!("/api/v1/graphs/CSIRO/changepoints?min_size={min_size}");
+        let res = test::call_service(&app, get(&uri)).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{uri}");
+    }
+}
+
+#[actix_web::test]
+async fn test_periodogram_query() {
+    use actix_web::{http::StatusCode, test};
+
+    let app = test::init_service(App::new().service(show_periodogram)).await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/api/v1/graphs/CSIRO/periodogram?samples=2")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    for query in [
+        "min_period=NaN",
+        "max_period=NaN",
+        "max_period=inf",
+        "min_period=-inf",
+        "samples=0",
+        "samples=1",
+    ] {
+        let uri = format!("/api/v1/graphs/CSIRO/periodogram?{query}");
+        let res = test::call_service(&app, get(&uri)).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{uri}");
+    }
+}
+
+#[actix_web::test]
+async fn test_decomposition_period() {
+    use actix_web::{http::StatusCode, test};
+
+    let app = test::init_service(App::new().service(show_decomposition)).await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/api/v1/graphs/CSIRO/decomposition?period=12")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let huge = format!("/api/v1/graphs/CSIRO/decomposition?period={}", usize::MAX);
+    assert_eq!(
+        test::call_service(&app, get(&huge)).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_web::test]
+async fn test_show_chart() {
+    use actix_web::{http::StatusCode, test};
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(CachePolicies::new(&[])))
+            .configure(routes),
+    )
+    .await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/api/v1/graphs/CSIRO.svg?graphs=UHSLC")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "image/svg+xml"
+    );
+    let svg = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(svg.contains("CSIRO, UHSLC") && svg.contains("Church, J. A."));
+
+    let res = test::call_service(&app, get("/api/v1/graphs/UHSLC.png?width=300&height=200")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "image/png"
+    );
+    let etag = res.headers().get(header::ETAG).unwrap().clone();
+    assert!(test::read_body(res).await.starts_with(b"\x89PNG"));
+    // Clients that already have the chart are answered without it
+    let req = test::TestRequest::get()
+        .uri("/api/v1/graphs/UHSLC.png?width=300&height=200")
+        .insert_header((header::IF_NONE_MATCH, etag.clone()))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(res.headers().get(header::ETAG), Some(&etag));
+    let req = test::TestRequest::get()
+        .uri("/api/v1/graphs/UHSLC.svg?width=300&height=200")
+        .insert_header((header::IF_NONE_MATCH, etag))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // The graphs themselves are still found by name, even with dots in it
+    let res = test::call_service(&app, get("/api/v1/graphs/CSIRO?format=json")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    for uri in [
+        "/api/v1/graphs/GISS%20v4.0",
+        "/api/v1/graphs/GISS%20v4.0.svg",
+    ] {
+        let res = test::call_service(&app, get(uri)).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let body = test::read_body(res).await;
+        assert_eq!(body, "no graph with name GISS v4.0", "{uri}");
+    }
+
+    for (uri, status) in [
+        ("/api/v1/graphs/CSIRO.gif", StatusCode::NOT_FOUND),
+        ("/api/v1/graphs/Nothing.svg", StatusCode::NOT_FOUND),
+        (
+            "/api/v1/graphs/CSIRO.svg?graphs=Nothing",
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            "/api/v1/graphs/CSIRO.svg?width=50000",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "/api/v1/graphs/CSIRO.png?width=4000&height=4000",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "/api/v1/graphs/CSIRO.svg?from=2000-01-01&to=1990-01-01",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "/api/v1/graphs/CSIRO.svg?from=2100-01-01",
+            StatusCode::BAD_REQUEST,
+        ),
+    ] {
+        assert_eq!(
+            test::call_service(&app, get(uri)).await.status(),
+            status,
+            "{uri}"
+        );
+    }
+}
+
+#[test]
+fn test_args() {
+    use clap::CommandFactory;
+    Args::command().debug_assert();
+
+    let parse = |args: &[&str]| {
+        Args::try_parse_from(std::iter::once("nchoputa").chain(args.iter().copied()))
+    };
+    assert!(matches!(
+        parse(&["--data-dir", "elsewhere", "serve", "--port", "2"]).unwrap(),
+        Args {
+            command: Some(Command::Serve(ServeArgs { port: 2, .. })),
+            ..
+        }
+    ));
+    let args = parse(&["--data-dir", "elsewhere", "list"]).unwrap();
+    assert_eq!(args.data_dir, PathBuf::from("elsewhere"));
+    // Server settings are not taken, and then ignored, by the other commands
+    assert!(parse(&["list", "--port", "1"]).is_err());
+    assert!(parse(&["--port", "1", "list"]).is_err());
 }
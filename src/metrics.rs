@@ -0,0 +1,132 @@
+use std::time::Instant;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error, get,
+    http::header::ContentType,
+    middleware::Next,
+    HttpResponse, Result,
+};
+use once_cell::sync::Lazy;
+use prometheus::{
+    core::Collector, Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry,
+    TextEncoder,
+};
+use tracing::error;
+
+static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+fn register<T: Collector + Clone + 'static>(metric: T) -> T {
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("metric names are unique");
+    metric
+}
+
+pub static REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    let opts = Opts::new(
+        "nchoputa_http_requests_total",
+        "Requests handled, by route and status",
+    );
+    register(IntCounterVec::new(opts, &["route", "status"]).unwrap())
+});
+
+pub static REQUEST_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    let opts = HistogramOpts::new(
+        "nchoputa_http_request_duration_seconds",
+        "Time taken to produce a response, by route",
+    );
+    register(HistogramVec::new(opts, &["route"]).unwrap())
+});
+
+pub static BYTES_SERVED: Lazy<IntCounterVec> = Lazy::new(|| {
+    let opts = Opts::new(
+        "nchoputa_dataset_bytes_served_total",
+        "Bytes of graph data sent before compression, by dataset",
+    );
+    register(IntCounterVec::new(opts, &["dataset"]).unwrap())
+});
+
+pub static DATASET_LOAD_SECONDS: Lazy<GaugeVec> = Lazy::new(|| {
+    let opts = Opts::new(
+        "nchoputa_dataset_load_duration_seconds",
+        "Time taken to load each dataset at startup",
+    );
+    register(GaugeVec::new(opts, &["dataset"]).unwrap())
+});
+
+pub static DATASET_LOAD_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    let opts = Opts::new(
+        "nchoputa_dataset_load_failures_total",
+        "Datasets that could not be loaded",
+    );
+    register(IntCounterVec::new(opts, &["dataset"]).unwrap())
+});
+
+/// Counts `bytes` of `dataset` as served, unless the client already had them.
+pub fn served(dataset: &str, response: &HttpResponse, bytes: usize) {
+    if response.status().is_success() {
+        BYTES_SERVED
+            .with_label_values(&[dataset])
+            .inc_by(bytes as u64);
+    }
+}
+
+/// Middleware counting and timing requests, labelled with the name of the handler that matched
+/// (`show_graph`, `static_file`, ...).
+pub async fn record(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>> {
+    let start = Instant::now();
+    let res = next.call(req).await?;
+
+    let request = res.request();
+    let route = request
+        .match_name()
+        .map(str::to_string)
+        .or_else(|| request.match_pattern())
+        .unwrap_or_else(|| "unmatched".to_string());
+    REQUESTS
+        .with_label_values(&[route.as_str(), res.status().as_str()])
+        .inc();
+    REQUEST_SECONDS
+        .with_label_values(&[route.as_str()])
+        .observe(start.elapsed().as_secs_f64());
+    Ok(res)
+}
+
+#[get("/metrics")]
+pub async fn show_metrics() -> Result<HttpResponse> {
+    let mut body = vec![];
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut body)
+        .map_err(|e| {
+            error!("error encoding metrics: {}", e);
+            error::ErrorInternalServerError("error encoding metrics")
+        })?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(body))
+}
+
+#[actix_web::test]
+async fn test_record() {
+    use actix_web::{middleware::from_fn, test, web, App};
+
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(record))
+            .service(show_metrics)
+            .route("/test", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    test::call_service(&app, test::TestRequest::get().uri("/test").to_request()).await;
+    test::call_service(&app, test::TestRequest::get().uri("/nowhere").to_request()).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains(r#"nchoputa_http_requests_total{route="/test",status="200"} 1"#));
+    assert!(body.contains(r#"nchoputa_http_requests_total{route="unmatched",status="404"} 1"#));
+}
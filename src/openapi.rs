@@ -0,0 +1,89 @@
+use actix_web::{get, http::header::ContentType, web, HttpRequest, HttpResponse};
+use once_cell::sync::Lazy;
+use utoipa::OpenApi;
+
+use crate::caching::{self, CachePolicies, Encoded};
+use crate::graphs;
+
+/// The API as described by the `#[utoipa::path]` on each handler, and the response types in
+/// `shared::response`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "nchoputa",
+        description = "Climate datasets and analyses of them. Responses are postcard unless a \
+            route takes a `format`, in which case `format=json` gets the same structure as JSON. \
+            Postcard responses start with the version of the schemas they were encoded with, as \
+            a postcard varint, which changes whenever they encode differently."
+    ),
+    paths(
+        crate::list_graphs,
+        crate::batch_graphs,
+        crate::show_graph,
+        crate::show_chart,
+        crate::show_quality,
+        crate::show_changepoints,
+        crate::show_decomposition,
+        crate::show_periodogram,
+        crate::show_forecast,
+        crate::compare_graphs,
+        crate::show_table,
+    ),
+    tags(
+        (name = "graphs", description = "The datasets themselves"),
+        (name = "analysis", description = "Statistics worked out from the datasets"),
+    )
+)]
+struct ApiDoc;
+
+/// Only changes with the binary, so it is built once.
+static DOCUMENT: Lazy<Encoded> = Lazy::new(|| {
+    let mut api = ApiDoc::openapi();
+    // Taken from the package, which doesn't set one
+    api.info.license = None;
+    Encoded::new(
+        api.to_pretty_json()
+            .expect("OpenAPI document encodes as JSON"),
+    )
+});
+
+#[get("/api/openapi.json")]
+pub async fn show_openapi(req: HttpRequest, policies: web::Data<CachePolicies>) -> HttpResponse {
+    caching::respond(
+        &req,
+        policies.get("show_openapi"),
+        *graphs::STARTED,
+        ContentType::json(),
+        &DOCUMENT,
+    )
+}
+
+#[test]
+fn test_document() {
+    let document: serde_json::Value = serde_json::from_slice(&DOCUMENT.body).unwrap();
+    let paths = document["paths"].as_object().unwrap();
+    assert!(paths.contains_key("/api/v1/graphs/{name}"));
+    assert!(paths.keys().all(|path| path.starts_with("/api/v1/")));
+
+    let show_graph = &paths["/api/v1/graphs/{name}"]["get"];
+    let mut parameters: Vec<&str> = show_graph["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|parameter| parameter["name"].as_str().unwrap())
+        .collect();
+    parameters.sort();
+    assert_eq!(parameters, ["fill", "format", "max_gap", "name"]);
+
+    // Everything the responses refer to is described
+    let schemas = document["components"]["schemas"].as_object().unwrap();
+    for schema in [
+        "GraphData",
+        "GraphList",
+        "Table",
+        "TableRow",
+        "ForecastPoint",
+    ] {
+        assert!(schemas.contains_key(schema), "{schema} is missing");
+    }
+}
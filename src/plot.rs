@@ -0,0 +1,139 @@
+use chrono::{Datelike, Months, NaiveDate};
+
+use shared::series::Point;
+
+pub mod braille;
+pub mod chart;
+
+/// A graph to be drawn, cut down to the dates being shown.
+#[derive(Clone, Copy, Debug)]
+pub struct Series<'a> {
+    pub name: &'a str,
+    pub color: (u8, u8, u8),
+    pub points: &'a [Point],
+}
+
+/// The range of values covered by `series`, or `None` if none of them have any points.
+pub fn value_range(series: &[Series]) -> Option<(f32, f32)> {
+    series
+        .iter()
+        .flat_map(|series| series.points.iter().map(|(_, value)| *value))
+        .fold(None, |range, value| match range {
+            None => Some((value, value)),
+            Some((min, max)) => Some((min.min(value), max.max(value))),
+        })
+}
+
+/// Evenly spaced round values for labelling an axis from `min` to `max`, at most about `most`
+/// of them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueTicks {
+    pub step: f64,
+    /// The first and last are the nearest round values outside of the range, so that an axis
+    /// extended to them starts and ends on a label.
+    pub values: Vec<f64>,
+}
+
+impl ValueTicks {
+    pub fn new(min: f64, max: f64, most: usize) -> Self {
+        let range = if max > min { max - min } else { 1.0 };
+        let rough = range / most.max(1) as f64;
+        let magnitude = 10f64.powf(rough.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0]
+            .iter()
+            .map(|mantissa| mantissa * magnitude)
+            .find(|step| *step >= rough)
+            .unwrap_or(10.0 * magnitude);
+
+        let first = (min / step).floor() as i64;
+        let last = ((if max > min { max } else { min + range }) / step).ceil() as i64;
+        ValueTicks {
+            step,
+            values: (first..=last).map(|i| i as f64 * step).collect(),
+        }
+    }
+
+    pub fn min(&self) -> f64 {
+        self.values[0]
+    }
+
+    pub fn max(&self) -> f64 {
+        self.values[self.values.len() - 1]
+    }
+
+    /// `value` with as many decimals as the step needs.
+    pub fn label(&self, value: f64) -> String {
+        let decimals = (-self.step.log10()).ceil().max(0.0) as usize;
+        format!("{value:.decimals$}")
+    }
+}
+
+/// The first of each month or year between `from` and `to`, spaced so that there are at most
+/// about `most`, each with a label of the year and, when the ticks are less than a year apart,
+/// the month.
+pub fn date_ticks(from: NaiveDate, to: NaiveDate, most: usize) -> Vec<(NaiveDate, String)> {
+    let months = (to.year() - from.year()) * 12 + to.month0() as i32 - from.month0() as i32 + 1;
+    let step = [1, 2, 3, 6, 12, 24, 60, 120, 240, 600, 1200]
+        .into_iter()
+        .find(|step| months / step <= most.max(1) as i32)
+        .unwrap_or(2400);
+
+    let mut ticks = vec![];
+    let mut date = NaiveDate::from_ymd_opt(from.year(), 1, 1).unwrap();
+    if step >= 12 {
+        let years = step / 12;
+        date = NaiveDate::from_ymd_opt(from.year().div_euclid(years) * years, 1, 1).unwrap();
+    }
+    while date <= to {
+        if date >= from {
+            let label = match step {
+                12.. => date.year().to_string(),
+                _ => date.format("%Y-%m").to_string(),
+            };
+            ticks.push((date, label));
+        }
+        date = match date.checked_add_months(Months::new(step as u32)) {
+            Some(date) => date,
+            None => break,
+        };
+    }
+    ticks
+}
+
+#[cfg(test)]
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[test]
+fn test_value_ticks() {
+    let ticks = ValueTicks::new(-159.7, 87.2, 5);
+    assert_eq!(ticks.step, 50.0);
+    assert_eq!(
+        ticks.values,
+        [-200.0, -150.0, -100.0, -50.0, 0.0, 50.0, 100.0]
+    );
+    assert_eq!(ticks.label(-150.0), "-150");
+
+    let ticks = ValueTicks::new(0.12, 0.31, 4);
+    assert_eq!(ticks.step, 0.05);
+    assert_eq!(ticks.label(ticks.min()), "0.10");
+
+    // A flat series still gets an axis
+    let ticks = ValueTicks::new(3.0, 3.0, 4);
+    assert!(ticks.min() <= 3.0 && ticks.max() > 3.0);
+}
+
+#[test]
+fn test_date_ticks() {
+    let ticks = date_ticks(date(1880, 4, 15), date(2013, 12, 15), 8);
+    let labels: Vec<&str> = ticks.iter().map(|(_, label)| label.as_str()).collect();
+    assert_eq!(labels, ["1900", "1920", "1940", "1960", "1980", "2000"]);
+
+    let ticks = date_ticks(date(2020, 2, 10), date(2020, 12, 31), 6);
+    let labels: Vec<&str> = ticks.iter().map(|(_, label)| label.as_str()).collect();
+    assert_eq!(
+        labels,
+        ["2020-03", "2020-05", "2020-07", "2020-09", "2020-11"]
+    );
+}
@@ -0,0 +1,220 @@
+//! Graphs drawn in the terminal with braille characters, each of which is a grid of two by four
+//! dots, for when there's no browser to hand.
+
+use std::fmt::Write;
+
+use chrono::NaiveDate;
+
+use super::{date_ticks, value_range, Series, ValueTicks};
+use crate::analysis::decimal_year;
+
+/// The bit of each dot in a braille character, by column and then row.
+const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+/// Braille cells, each coloured by the last series to draw in it.
+struct Canvas {
+    columns: usize,
+    rows: usize,
+    cells: Vec<(u32, Option<usize>)>,
+}
+
+impl Canvas {
+    fn new(columns: usize, rows: usize) -> Self {
+        Canvas {
+            columns,
+            rows,
+            cells: vec![(0, None); columns * rows],
+        }
+    }
+
+    /// Sets the dot at `x`, `y` counted from the top left, ignoring any outside the canvas.
+    fn set(&mut self, x: i64, y: i64, series: usize) {
+        if x < 0 || y < 0 || x >= 2 * self.columns as i64 || y >= 4 * self.rows as i64 {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let cell = &mut self.cells[y / 4 * self.columns + x / 2];
+        cell.0 |= DOTS[x % 2][y % 4];
+        cell.1 = Some(series);
+    }
+
+    fn line(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64), series: usize) {
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = ((x1 - x0).signum(), (y1 - y0).signum());
+        let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+        loop {
+            self.set(x, y, series);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += sx;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+}
+
+fn paint(out: &mut String, text: &str, color: Option<(u8, u8, u8)>) {
+    match color {
+        Some((r, g, b)) => write!(out, "\x1b[38;2;{r};{g};{b}m{text}\x1b[0m").unwrap(),
+        None => out.push_str(text),
+    }
+}
+
+/// Draws `series` from `from` to `to` in a block of text `width` by `height` characters, with
+/// the value axis on the left, dates along the bottom and a legend under that. The lines and
+/// legend are in each series' color if `color` is set, using 24-bit ANSI escapes.
+pub fn render(
+    series: &[Series],
+    from: NaiveDate,
+    to: NaiveDate,
+    (width, height): (usize, usize),
+    color: bool,
+) -> String {
+    let Some((min, max)) = value_range(series) else {
+        return "nothing to plot\n".to_string();
+    };
+    // Two rows under the plot for the axis and its labels, and one per series for the legend
+    let rows = height.saturating_sub(2 + series.len()).max(3);
+    let ticks = ValueTicks::new(min as f64, max as f64, (rows / 3).max(2));
+    let labels: Vec<String> = ticks
+        .values
+        .iter()
+        .map(|value| ticks.label(*value))
+        .collect();
+    let margin = labels.iter().map(String::len).max().unwrap_or(0);
+    let columns = width.saturating_sub(margin + 2).max(10);
+
+    let (dots_x, dots_y) = (2 * columns as i64 - 1, 4 * rows as i64 - 1);
+    let (start, end) = (decimal_year(&from), decimal_year(&to));
+    let x = |date: &NaiveDate| {
+        let span = if end > start { end - start } else { 1.0 };
+        ((decimal_year(date) - start) / span * dots_x as f64).round() as i64
+    };
+    let y = |value: f64| {
+        ((ticks.max() - value) / (ticks.max() - ticks.min()) * dots_y as f64).round() as i64
+    };
+
+    let mut canvas = Canvas::new(columns, rows);
+    for (i, series) in series.iter().enumerate() {
+        let dots: Vec<(i64, i64)> = series
+            .points
+            .iter()
+            .map(|(date, value)| (x(date), y(*value as f64)))
+            .collect();
+        match dots.as_slice() {
+            [dot] => canvas.set(dot.0, dot.1, i),
+            dots => {
+                for pair in dots.windows(2) {
+                    canvas.line(pair[0], pair[1], i);
+                }
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for row in 0..rows {
+        let label = ticks
+            .values
+            .iter()
+            .zip(&labels)
+            .find(|(value, _)| y(**value) / 4 == row as i64)
+            .map(|(_, label)| label.as_str());
+        match label {
+            Some(label) => write!(out, "{label:>margin$} ┤").unwrap(),
+            None => write!(out, "{:>margin$} │", "").unwrap(),
+        }
+        for column in 0..columns {
+            let (bits, owner) = canvas.cells[row * columns + column];
+            let cell = char::from_u32(0x2800 + bits).unwrap().to_string();
+            let cell_color = owner.filter(|_| color).map(|i| series[i].color);
+            paint(&mut out, &cell, cell_color);
+        }
+        out.push('\n');
+    }
+
+    // The date axis, with a tick above each label that fits without running into the one before
+    let mut axis: Vec<char> = "─".repeat(columns).chars().collect();
+    let mut tick_labels = vec![' '; columns];
+    let mut free = 0;
+    for (date, label) in date_ticks(from, to, columns / 8) {
+        // A label at the right hand end is moved left to end under its tick
+        let column = (x(&date) / 2) as usize;
+        let start = column.min(columns.saturating_sub(label.len()));
+        if start < free {
+            continue;
+        }
+        axis[column] = '┬';
+        tick_labels.splice(start..start + label.len(), label.chars());
+        free = start + label.len() + 1;
+    }
+    writeln!(out, "{:>margin$} └{}", "", axis.iter().collect::<String>()).unwrap();
+    writeln!(
+        out,
+        "{:>margin$}  {}",
+        "",
+        tick_labels.iter().collect::<String>().trim_end()
+    )
+    .unwrap();
+
+    for series in series {
+        write!(out, "{:>margin$}  ", "").unwrap();
+        paint(&mut out, "⣿⣿", color.then_some(series.color));
+        writeln!(out, " {}", series.name).unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+fn date(year: i32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, 1, 1).unwrap()
+}
+
+#[test]
+fn test_render() {
+    let rising = [(date(2000), 0.0), (date(2010), 10.0), (date(2020), 20.0)];
+    let falling = [(date(2000), 20.0), (date(2020), 0.0)];
+    let series = [
+        Series {
+            name: "Rising",
+            color: (0xB1, 0xF8, 0xF2),
+            points: &rising,
+        },
+        Series {
+            name: "Falling",
+            color: (0xBC, 0xD3, 0x9C),
+            points: &falling,
+        },
+    ];
+
+    let plain = render(&series, date(2000), date(2020), (40, 14), false);
+    let lines: Vec<&str> = plain.lines().collect();
+    assert_eq!(lines.len(), 14);
+    assert!(lines.iter().all(|line| line.chars().count() <= 40));
+    assert!(!plain.contains('\x1b'));
+    // Values down the side, with the top of the axis at the top
+    assert!(lines[0].starts_with("20 ┤"));
+    assert!(lines[9].contains("0 ┤"));
+    // Dates along the bottom, and a legend
+    assert!(lines[10].contains('└') && lines[10].contains('┬'));
+    assert!(lines[11].contains("2000") && lines[11].contains("2020"));
+    assert!(lines[12].ends_with("⣿⣿ Rising") && lines[13].ends_with("⣿⣿ Falling"));
+    // The lines cross in the middle, where both series draw
+    assert!(
+        plain
+            .chars()
+            .filter(|c| ('\u{2801}'..='\u{28FF}').contains(c))
+            .count()
+            > 20
+    );
+
+    let colored = render(&series, date(2000), date(2020), (40, 14), true);
+    assert!(colored.contains("\x1b[38;2;177;248;242m"));
+    assert!(colored.contains("\x1b[38;2;188;211;156m"));
+}
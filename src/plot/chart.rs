@@ -0,0 +1,312 @@
+//! Charts drawn on the server as SVG or PNG, for places the viewer can't run such as reports and
+//! link previews. They use the same colors and font as the site.
+
+use std::io::Cursor;
+use std::ops::Range;
+
+use chrono::NaiveDate;
+use image::{ImageFormat, RgbImage};
+use once_cell::sync::Lazy;
+use plotters::coord::ranged1d::{DefaultFormatting, KeyPointHint, Ranged};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::{register_font, FontStyle};
+
+use super::{date_ticks, value_range, Series, ValueTicks};
+use crate::analysis::decimal_year;
+
+/// The name the font is registered under, which is also what SVGs ask for.
+const FONT: &str = "Fira Mono";
+/// The width of a character as a fraction of the font size, which is the same for every
+/// character since the font is monospaced.
+const ADVANCE: f64 = 0.6;
+
+const BACKGROUND: RGBColor = RGBColor(0x00, 0x00, 0x00);
+const TEXT: RGBColor = RGBColor(0xEA, 0xFD, 0xCF);
+const MUTED: RGBColor = RGBColor(0x8E, 0x83, 0x58);
+
+const FOOTER_FONT_SIZE: u32 = 12;
+const FOOTER_LINE_HEIGHT: u32 = 16;
+
+/// Registered once, for every chart drawn after.
+static FONT_REGISTERED: Lazy<()> = Lazy::new(|| {
+    let bytes = include_bytes!("../../static/FiraMono-Medium.ttf");
+    register_font(FONT, FontStyle::Normal, bytes)
+        .unwrap_or_else(|_| panic!("{FONT} is not a valid font"));
+});
+
+/// A linear axis with grid lines at ticks worked out here rather than by plotters, so that they
+/// fall in the same places as in the terminal.
+struct Ticked {
+    range: Range<f64>,
+    ticks: Vec<f64>,
+}
+
+impl Ranged for Ticked {
+    type FormatOption = DefaultFormatting;
+    type ValueType = f64;
+
+    fn map(&self, value: &f64, (start, end): (i32, i32)) -> i32 {
+        let span = self.range.end - self.range.start;
+        let fraction = if span > 0.0 {
+            (value - self.range.start) / span
+        } else {
+            0.0
+        };
+        start + (fraction * (end - start) as f64).round() as i32
+    }
+
+    fn key_points<Hint: KeyPointHint>(&self, _hint: Hint) -> Vec<f64> {
+        self.ticks.clone()
+    }
+
+    fn range(&self) -> Range<f64> {
+        self.range.clone()
+    }
+}
+
+/// What a chart shows, besides the lines themselves.
+#[derive(Clone, Debug)]
+pub struct Chart<'a> {
+    pub series: &'a [Series<'a>],
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Labels the value axis.
+    pub units: String,
+    /// Credits for the data, each given its own lines at the bottom.
+    pub citations: Vec<String>,
+}
+
+impl Chart<'_> {
+    /// The chart as an SVG document `width` by `height` pixels.
+    pub fn svg(&self, (width, height): (u32, u32)) -> Result<String, String> {
+        let mut svg = String::new();
+        {
+            let root = SVGBackend::with_string(&mut svg, (width, height)).into_drawing_area();
+            self.draw(&root).map_err(|e| e.to_string())?;
+            root.present().map_err(|e| e.to_string())?;
+        }
+        Ok(svg)
+    }
+
+    /// The chart as a PNG image `width` by `height` pixels.
+    pub fn png(&self, (width, height): (u32, u32)) -> Result<Vec<u8>, String> {
+        let mut pixels = vec![0; width as usize * height as usize * 3];
+        {
+            let root = BitMapBackend::with_buffer(&mut pixels, (width, height)).into_drawing_area();
+            self.draw(&root).map_err(|e| e.to_string())?;
+            root.present().map_err(|e| e.to_string())?;
+        }
+        let image = RgbImage::from_raw(width, height, pixels).ok_or("pixels do not fit")?;
+        let mut png = Cursor::new(vec![]);
+        image
+            .write_to(&mut png, ImageFormat::Png)
+            .map_err(|e| e.to_string())?;
+        Ok(png.into_inner())
+    }
+
+    fn draw<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+    ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        Lazy::force(&FONT_REGISTERED);
+        let (width, height) = root.dim_in_pixel();
+        root.fill(&BACKGROUND)?;
+
+        // The citations are wrapped to fit across the bottom, and the chart takes what's left
+        let columns = ((width as f64 - 32.0) / (FOOTER_FONT_SIZE as f64 * ADVANCE)) as usize;
+        let footer: Vec<String> = self
+            .citations
+            .iter()
+            .flat_map(|citation| wrap(citation, columns.max(20)))
+            .collect();
+        let footer_height = match footer.len() {
+            0 => 0,
+            lines => lines as u32 * FOOTER_LINE_HEIGHT + 12,
+        };
+        let (plot, credits) = root.split_vertically(height.saturating_sub(footer_height));
+        let footer_style = (FONT, FOOTER_FONT_SIZE).into_font().color(&MUTED);
+        for (i, line) in footer.iter().enumerate() {
+            let y = 4 + i as i32 * FOOTER_LINE_HEIGHT as i32;
+            credits.draw_text(line, &footer_style, (16, y))?;
+        }
+
+        let (min, max) = value_range(self.series).unwrap_or((0.0, 1.0));
+        let plot_height = height.saturating_sub(footer_height);
+        let value_ticks =
+            ValueTicks::new(min as f64, max as f64, (plot_height / 60).max(2) as usize);
+        let date_ticks: Vec<(f64, String)> = date_ticks(self.from, self.to, (width / 110) as usize)
+            .into_iter()
+            .map(|(date, label)| (decimal_year(&date), label))
+            .collect();
+        let value_labels: Vec<String> = value_ticks
+            .values
+            .iter()
+            .map(|value| value_ticks.label(*value))
+            .collect();
+        let label_width = value_labels.iter().map(String::len).max().unwrap_or(1);
+
+        let title = self
+            .series
+            .iter()
+            .map(|series| series.name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let label_style = (FONT, 14).into_font().color(&TEXT);
+        let mut chart = ChartBuilder::on(&plot)
+            .margin(16)
+            // Room for the last date to be centered on its tick
+            .margin_right(32)
+            .caption(title, (FONT, 20).into_font().color(&TEXT))
+            .x_label_area_size(32)
+            .y_label_area_size(label_width as u32 * 9 + 36)
+            .build_cartesian_2d(
+                Ticked {
+                    range: decimal_year(&self.from)..decimal_year(&self.to),
+                    ticks: date_ticks.iter().map(|(x, _)| *x).collect(),
+                },
+                Ticked {
+                    range: value_ticks.min()..value_ticks.max(),
+                    ticks: value_ticks.values.clone(),
+                },
+            )?;
+        chart
+            .configure_mesh()
+            .bold_line_style(MUTED.mix(0.3))
+            .light_line_style(TRANSPARENT)
+            .axis_style(MUTED)
+            .label_style(label_style.clone())
+            .axis_desc_style(label_style.clone())
+            .x_label_formatter(&|x| {
+                date_ticks
+                    .iter()
+                    .find(|(tick, _)| (tick - x).abs() < 1e-9)
+                    .map(|(_, label)| label.clone())
+                    .unwrap_or_default()
+            })
+            .y_label_formatter(&|y| value_ticks.label(*y))
+            .y_desc(self.units.as_str())
+            .draw()?;
+
+        for series in self.series {
+            let (r, g, b) = series.color;
+            let color = RGBColor(r, g, b);
+            let points = series
+                .points
+                .iter()
+                .map(|(date, value)| (decimal_year(date), *value as f64));
+            chart
+                .draw_series(LineSeries::new(points, color.stroke_width(2)))?
+                .label(series.name)
+                .legend(move |(x, y)| {
+                    PathElement::new([(x, y), (x + 20, y)], color.stroke_width(2))
+                });
+        }
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(BACKGROUND.mix(0.8))
+            .border_style(MUTED)
+            .label_font(label_style)
+            .draw()
+    }
+}
+
+/// Splits `text` into lines of at most `columns` characters, between words where it can.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word;
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > columns {
+            lines.push(std::mem::take(&mut line));
+        }
+        // Words that are longer than a whole line, such as URLs, are broken up
+        while word.chars().count() > columns {
+            let split = word
+                .char_indices()
+                .nth(columns)
+                .map_or(word.len(), |(i, _)| i);
+            lines.push(word[..split].to_string());
+            word = &word[split..];
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+fn date(year: i32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, 1, 1).unwrap()
+}
+
+#[test]
+fn test_wrap() {
+    assert_eq!(
+        wrap(
+            "Sea-Level Rise from the Late 19th to the Early 21st Century",
+            20
+        ),
+        [
+            "Sea-Level Rise from",
+            "the Late 19th to the",
+            "Early 21st Century"
+        ]
+    );
+    assert_eq!(
+        wrap("see https://doi.org/10.1007/s10712", 12),
+        ["see", "https://doi.", "org/10.1007/", "s10712"]
+    );
+    assert!(wrap("", 10).is_empty());
+}
+
+#[test]
+fn test_render() {
+    let rising = [(date(2000), 0.0), (date(2010), 10.0), (date(2020), 20.0)];
+    let series = [Series {
+        name: "Rising",
+        color: (0xB1, 0xF8, 0xF2),
+        points: &rising,
+    }];
+    let chart = Chart {
+        series: &series,
+        from: date(2000),
+        to: date(2020),
+        units: "mm".to_string(),
+        citations: vec!["Rising: a paper about sea level".to_string()],
+    };
+
+    let svg = chart.svg((800, 450)).unwrap();
+    assert!(svg.starts_with("<svg") && svg.contains(r#"width="800""#));
+    let texts: Vec<&str> = svg
+        .split("<text")
+        .skip(1)
+        .filter_map(|text| text.split_once('>'))
+        .filter_map(|(_, rest)| rest.split_once("</text>"))
+        .map(|(text, _)| text.trim())
+        .collect();
+    for text in [
+        "Rising",
+        "mm",
+        "2010",
+        "20",
+        "Rising: a paper about sea level",
+    ] {
+        assert!(texts.contains(&text), "{text} is missing from the SVG");
+    }
+    assert!(svg.contains(r#"font-family="Fira Mono""#));
+    assert!(svg.contains("#B1F8F2"));
+
+    let png = chart.png((800, 450)).unwrap();
+    let image = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+    assert_eq!((image.width(), image.height()), (800, 450));
+    // The line is drawn in its color
+    let rgb = image.to_rgb8();
+    assert!(rgb.pixels().any(|pixel| pixel.0 == [0xB1, 0xF8, 0xF2]));
+}
@@ -0,0 +1,116 @@
+use actix_web::http::header::HeaderMap;
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::Context;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+
+/// Exports spans in batches to the OTLP/HTTP collector at `endpoint`, such as
+/// `http://localhost:4318`.
+///
+/// The exporter uses a blocking HTTP client on its own thread, so this has to be called outside
+/// of the server's async runtime.
+pub fn provider(endpoint: &str) -> Result<SdkTracerProvider, ExporterBuildError> {
+    // When reqwest is built with rustls for `shared`'s client, it's left to whoever uses it to
+    // pick a crypto provider, so pick ring as `tls` does
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("nchoputa").build())
+        .build())
+}
+
+/// The trace a request belongs to according to its W3C `traceparent` and `tracestate` headers,
+/// or an empty context to start a new one.
+pub fn parent_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&Headers(headers))
+}
+
+struct Headers<'a>(&'a HeaderMap);
+
+impl Extractor for Headers<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Accepts a single OTLP/HTTP request, standing in for a collector, and returns its path and
+/// body.
+#[cfg(test)]
+fn collect_one(listener: std::net::TcpListener) -> std::thread::JoinHandle<(String, Vec<u8>)> {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        reader
+            .into_inner()
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .unwrap();
+        let path = request_line.split(' ').nth(1).unwrap().to_string();
+        (path, body)
+    })
+}
+
+#[test]
+fn test_export() {
+    use opentelemetry::trace::TracerProvider;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let collector = collect_one(listener);
+
+    let provider = provider(&endpoint).unwrap();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    tracing::subscriber::with_default(subscriber, || {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            actix_web::http::header::HeaderName::from_static("traceparent"),
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+        let span = tracing::info_span!("load dataset");
+        span.set_parent(parent_context(&headers)).unwrap();
+        span.in_scope(|| {});
+    });
+    provider.force_flush().unwrap();
+
+    let (path, body) = collector.join().unwrap();
+    assert_eq!(path, "/v1/traces");
+    let contains = |needle: &[u8]| body.windows(needle.len()).any(|window| window == needle);
+    assert!(contains(b"load dataset"));
+    assert!(contains(&[
+        0x0a, 0xf7, 0x65, 0x19, 0x16, 0xcd, 0x43, 0xdd, 0x84, 0x48, 0xeb, 0x21, 0x1c, 0x80, 0x31,
+        0x9c
+    ]));
+}
@@ -0,0 +1,38 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+
+/// Configuration for serving HTTPS with the PEM certificate chain in `cert` and the private key
+/// in `key`.
+pub fn server_config(cert: &Path, key: &Path) -> io::Result<ServerConfig> {
+    let invalid = |path: &Path, e: &dyn std::fmt::Display| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {e}", path.display()),
+        )
+    };
+
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(cert, &e))?;
+    if certs.is_empty() {
+        return Err(invalid(cert, &"no certificates found"));
+    }
+    let private_key = PrivateKeyDer::from_pem_file(key).map_err(|e| invalid(key, &e))?;
+
+    ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| invalid(cert, &e))?
+        .with_no_client_auth()
+        .with_single_cert(certs, private_key)
+        .map_err(|e| invalid(cert, &e))
+}
+
+#[test]
+fn test_missing_files() {
+    let e = server_config(Path::new("missing.pem"), Path::new("missing.key")).unwrap_err();
+    assert!(e.to_string().starts_with("missing.pem"));
+}
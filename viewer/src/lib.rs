@@ -19,9 +19,9 @@ use bevy::{
 };
 use bevy_egui::{egui, EguiContexts, EguiPlugin, EguiPrimaryContextPass};
 use chrono::NaiveDate;
-use postcard::from_bytes;
-use serde::{Deserialize, Serialize};
-use shared::response::{GraphData, GraphList, GraphSummary};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shared::envelope;
+use shared::response::{ChangePoints, GraphData, GraphList, GraphSummary, Periodogram};
 
 mod wasm {
 
@@ -90,8 +90,14 @@ pub fn main() {
         Update,
         (
             on_resize,
-            graph_added_listener,
-            graph_removed_listener,
+            // Change points are drawn against their graph, so it has to be spawned first
+            (
+                graph_added_listener,
+                graph_removed_listener,
+                changepoints_added_listener,
+                changepoints_removed_listener,
+            )
+                .chain(),
             on_mousewheel,
             on_mousemotion,
         ),
@@ -99,6 +105,8 @@ pub fn main() {
     .add_systems(EguiPrimaryContextPass, ui)
     .add_event::<EventGraphAdded>()
     .add_event::<EventGraphRemoved>()
+    .add_event::<EventChangePointsAdded>()
+    .add_event::<EventChangePointsRemoved>()
     .run();
 
     tracing::info!("start up complete");
@@ -113,6 +121,9 @@ struct GraphPoints(Vec<(f32, f32)>);
 #[derive(Component, Debug)]
 struct GraphLabels(Vec<(NaiveDate, f32)>);
 
+#[derive(Component, Debug)]
+struct ChangePointMarker;
+
 #[derive(Resource)]
 struct State {
     startup: bool,
@@ -122,6 +133,10 @@ struct State {
     graphs: Arc<Mutex<HashMap<String, GraphSummary>>>,
     loaded_graphs: Arc<Mutex<HashMap<String, GraphData>>>,
     unloaded_graphs: Arc<Mutex<Vec<String>>>,
+    show_changepoints: Arc<AtomicBool>,
+    loaded_changepoints: Arc<Mutex<HashMap<String, ChangePoints>>>,
+    show_periodogram: Arc<AtomicBool>,
+    periodograms: Arc<Mutex<HashMap<String, Periodogram>>>,
 }
 
 impl State {
@@ -134,6 +149,10 @@ impl State {
             graphs: default(),
             loaded_graphs: default(),
             unloaded_graphs: default(),
+            show_changepoints: default(),
+            loaded_changepoints: default(),
+            show_periodogram: default(),
+            periodograms: default(),
         }
     }
 }
@@ -149,6 +168,262 @@ struct EventGraphRemoved {
     graph_name: String,
 }
 
+#[derive(Event)]
+struct EventChangePointsAdded {
+    changepoints: ChangePoints,
+}
+
+#[derive(Event)]
+struct EventChangePointsRemoved;
+
+/// Why a fetch failed, including the ID the server logged the request under if it got that far.
+fn failure(result: &ehttp::Result<ehttp::Response>) -> String {
+    match result {
+        Ok(response) => match response.headers.get("x-request-id") {
+            Some(id) => format!(
+                "{} {} (request {})",
+                response.status, response.status_text, id
+            ),
+            None => format!("{} {}", response.status, response.status_text),
+        },
+        Err(e) => e.clone(),
+    }
+}
+
+/// Set once the server has answered with another version of the response types, after which
+/// nothing more it sends can be read until the page is reloaded.
+static OUTDATED: AtomicBool = AtomicBool::new(false);
+
+/// Decodes a postcard response, or logs why it couldn't, `what` describes it.
+fn decode<T: DeserializeOwned>(what: &str, bytes: &[u8]) -> Option<T> {
+    match envelope::open(bytes) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            tracing::warn!("error decoding {}: {}", what, e);
+            if let envelope::Error::Version { .. } = e {
+                OUTDATED.store(true, Ordering::SeqCst);
+            }
+            None
+        }
+    }
+}
+
+fn fetch_changepoints(name: &str, loaded_changepoints: Arc<Mutex<HashMap<String, ChangePoints>>>) {
+    let request = ehttp::Request::get(format!("/api/v1/graphs/{name}/changepoints"));
+    let name = name.to_string();
+    ehttp::fetch(
+        request,
+        move |result: ehttp::Result<ehttp::Response>| match result {
+            Ok(v) if v.status == 200 => {
+                if let Some(changepoints) = decode::<ChangePoints>("change points", &v.bytes) {
+                    loaded_changepoints
+                        .lock()
+                        .unwrap()
+                        .insert(name, changepoints);
+                }
+            }
+            result => {
+                tracing::warn!(
+                    "error loading change points for {}: {}",
+                    name,
+                    failure(&result)
+                );
+            }
+        },
+    );
+}
+
+/// Fetches the periodogram of `name`, which is dropped if the graph has been unticked by the
+/// time it arrives.
+fn fetch_periodogram(
+    name: &str,
+    graphs: Arc<Mutex<HashMap<String, GraphSummary>>>,
+    periodograms: Arc<Mutex<HashMap<String, Periodogram>>>,
+) {
+    let request = ehttp::Request::get(format!("/api/v1/graphs/{name}/periodogram"));
+    let name = name.to_string();
+    ehttp::fetch(
+        request,
+        move |result: ehttp::Result<ehttp::Response>| match result {
+            Ok(v) if v.status == 200 => {
+                let Some(periodogram) = decode::<Periodogram>("periodogram", &v.bytes) else {
+                    return;
+                };
+                if graphs.lock().unwrap().contains_key(&name) {
+                    periodograms.lock().unwrap().insert(name, periodogram);
+                }
+            }
+            result => {
+                tracing::warn!(
+                    "error loading periodogram for {}: {}",
+                    name,
+                    failure(&result)
+                );
+            }
+        },
+    );
+}
+
+/// Plots power against period on a log axis, so that cycles of a few years (ENSO) and of a few
+/// decades (the nodal cycle) are both legible.
+fn periodogram_window(ctx: &egui::Context, periodograms: &HashMap<String, Periodogram>) {
+    egui::Window::new("Periodogram")
+        .resizable(false)
+        .movable(true)
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-100.0, -100.0])
+        .show(ctx, |ui| {
+            let (response, painter) =
+                ui.allocate_painter(egui::vec2(480.0, 240.0), egui::Sense::hover());
+            let margin = 30.0;
+            let plot = egui::Rect::from_min_max(
+                response.rect.min + egui::vec2(margin, 0.0),
+                response.rect.max - egui::vec2(0.0, margin),
+            );
+            let axis = egui::Stroke::new(1.0, egui::Color32::GRAY);
+            painter.line_segment([plot.left_top(), plot.left_bottom()], axis);
+            painter.line_segment([plot.left_bottom(), plot.right_bottom()], axis);
+
+            let points = periodograms.values().flat_map(|p| p.points.iter());
+            let (min_period, max_period, max_power) = points.fold(
+                (f32::MAX, f32::MIN, 0.0f32),
+                |(min, max, power), (period, p)| {
+                    (min.min(*period), max.max(*period), power.max(*p))
+                },
+            );
+            if min_period <= 0.0 || max_period <= min_period || max_power <= 0.0 {
+                return;
+            }
+            let (log_min, log_max) = (min_period.log10(), max_period.log10());
+            let to_screen = |period: f32, power: f32| {
+                egui::pos2(
+                    plot.left() + (period.log10() - log_min) / (log_max - log_min) * plot.width(),
+                    plot.bottom() - power / max_power * plot.height(),
+                )
+            };
+
+            // Label 1, 2 and 5 of every decade that falls within the range
+            for exponent in log_min.floor() as i32..=log_max.ceil() as i32 {
+                for mantissa in [1.0, 2.0, 5.0] {
+                    let period = mantissa * 10f32.powi(exponent);
+                    if period < min_period || period > max_period {
+                        continue;
+                    }
+                    let tick = to_screen(period, 0.0);
+                    painter.line_segment([tick, tick + egui::vec2(0.0, 5.0)], axis);
+                    painter.text(
+                        tick + egui::vec2(0.0, 7.0),
+                        egui::Align2::CENTER_TOP,
+                        format!("{period}"),
+                        egui::FontId::monospace(10.0),
+                        egui::Color32::GRAY,
+                    );
+                }
+            }
+            painter.text(
+                plot.right_bottom() + egui::vec2(0.0, margin),
+                egui::Align2::RIGHT_BOTTOM,
+                "period (years)",
+                egui::FontId::monospace(10.0),
+                egui::Color32::GRAY,
+            );
+
+            for periodogram in periodograms.values() {
+                let (r, g, b) = periodogram.color;
+                painter.line(
+                    periodogram
+                        .points
+                        .iter()
+                        .map(|(period, power)| to_screen(*period, *power))
+                        .collect(),
+                    egui::Stroke::new(1.0, egui::Color32::from_rgb(r, g, b)),
+                );
+            }
+        });
+}
+
+/// The graphs named in the page's `?graphs=` parameter, so that a view can be shared as a link.
+fn graphs_from_location() -> Vec<String> {
+    web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .and_then(|search| web_sys::UrlSearchParams::new_with_str(&search).ok())
+        .and_then(|params| params.get("graphs"))
+        .map(|names| {
+            names
+                .split(',')
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Keeps `?graphs=` in step with the selection, without adding to the browser history.
+fn update_location<'a>(names: impl Iterator<Item = &'a String>) {
+    let mut names: Vec<&str> = names.map(String::as_str).collect();
+    names.sort_unstable();
+
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let search = window.location().search().unwrap_or_default();
+    let Ok(params) = web_sys::UrlSearchParams::new_with_str(&search) else {
+        return;
+    };
+    if names.is_empty() {
+        params.delete("graphs");
+    } else {
+        params.set("graphs", &names.join(","));
+    }
+
+    let query = String::from(params.to_string());
+    let url = if query.is_empty() {
+        window.location().pathname().unwrap_or_default()
+    } else {
+        format!("?{query}")
+    };
+    if let Ok(history) = window.history() {
+        let state = wasm_bindgen::JsValue::NULL;
+        if let Err(e) = history.replace_state_with_url(&state, "", Some(&url)) {
+            tracing::warn!("error updating location: {:?}", e);
+        }
+    }
+}
+
+/// Fetches several graphs in one request, unticking them again if that fails.
+fn fetch_batch(
+    names: Vec<String>,
+    graphs: Arc<Mutex<HashMap<String, GraphSummary>>>,
+    fetching_graphs: Arc<Mutex<HashMap<String, String>>>,
+    loaded_graphs: Arc<Mutex<HashMap<String, GraphData>>>,
+) {
+    let request = ehttp::Request::get(format!("/api/v1/graphs/batch?names={}", names.join(",")));
+    ehttp::fetch(request, move |result: ehttp::Result<ehttp::Response>| {
+        let mut fetching = fetching_graphs.lock().unwrap();
+        let batch = match &result {
+            Ok(v) if v.status == 200 => decode::<Vec<GraphData>>("graphs", &v.bytes),
+            _ => None,
+        };
+        match batch {
+            Some(batch) => {
+                let mut loaded = loaded_graphs.lock().unwrap();
+                for graph in batch {
+                    fetching.remove(&graph.name);
+                    loaded.insert(graph.name.clone(), graph);
+                }
+            }
+            None => {
+                tracing::warn!("error loading graphs {:?}: {}", names, failure(&result));
+                let mut graphs = graphs.lock().unwrap();
+                for name in names.iter() {
+                    fetching.remove(name);
+                    graphs.remove(name);
+                }
+                update_location(graphs.keys());
+            }
+        }
+    });
+}
+
 // thoughts:
 // egui is immediate, bevy is not, this is a slight impedance mismatch
 fn ui(
@@ -156,6 +431,8 @@ fn ui(
     mut state: ResMut<State>,
     mut added_events: EventWriter<EventGraphAdded>,
     mut removed_events: EventWriter<EventGraphRemoved>,
+    mut changepoints_added_events: EventWriter<EventChangePointsAdded>,
+    mut changepoints_removed_events: EventWriter<EventChangePointsRemoved>,
 ) {
     if state.startup {
         tracing::trace!("performing ui startup");
@@ -163,23 +440,64 @@ fn ui(
 
         let graph_list = state.graph_list.clone();
         let legend_bool = state.loaded_legend.clone();
+        let graphs = state.graphs.clone();
+        let fetching_graphs = state.fetching_graphs.clone();
+        let loaded_graphs = state.loaded_graphs.clone();
 
-        let request = ehttp::Request::get("/api/graphs");
+        let request = ehttp::Request::get("/api/v1/graphs");
         ehttp::fetch(request, move |result: ehttp::Result<ehttp::Response>| {
             match result {
                 Ok(v) if v.status == 200 => {
-                    let list: GraphList = from_bytes(&v.bytes).unwrap();
+                    let Some(list) = decode::<GraphList>("legend", &v.bytes) else {
+                        return;
+                    };
                     tracing::info!("server responded with legend = {:?}", &list);
+
+                    // Restore the selection from a shared link in one request
+                    let restore: Vec<&GraphSummary> = graphs_from_location()
+                        .iter()
+                        .filter_map(|name| list.graphs.iter().find(|graph| &graph.name == name))
+                        .collect();
+                    if !restore.is_empty() {
+                        let mut selected = graphs.lock().unwrap();
+                        let mut fetching = fetching_graphs.lock().unwrap();
+                        for graph in restore.iter() {
+                            selected.insert(graph.name.clone(), (*graph).clone());
+                            fetching.insert(graph.name.clone(), graph.uri.clone());
+                        }
+                        fetch_batch(
+                            restore.iter().map(|graph| graph.name.clone()).collect(),
+                            graphs.clone(),
+                            fetching_graphs.clone(),
+                            loaded_graphs,
+                        );
+                    }
+
                     *graph_list.lock().unwrap() = Some(list);
                 }
-                _ => {
-                    tracing::warn!("error loading legend");
+                result => {
+                    tracing::warn!("error loading legend: {}", failure(&result));
                 }
             }
             legend_bool.store(true, Ordering::SeqCst);
         });
     }
 
+    if OUTDATED.load(Ordering::SeqCst) {
+        egui::Window::new("Out of date")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(egui_context.ctx_mut().unwrap(), |ui| {
+                ui.label("The server has been updated since this page loaded, please reload it.");
+                if ui.button("Reload").clicked() {
+                    if let Some(window) = web_sys::window() {
+                        let _ = window.location().reload();
+                    }
+                }
+            });
+    }
+
     if state.loaded_legend.load(Ordering::SeqCst) {
         let graph_list = state.graph_list.clone();
         let fetching_graphs = state.fetching_graphs.clone();
@@ -215,43 +533,123 @@ fn ui(
                                     let uri = &graph.uri;
                                     graphs.insert(label.clone(), graph.clone());
                                     fetching.insert(label.clone(), uri.clone());
+                                    update_location(graphs.keys());
 
                                     let request = ehttp::Request::get(uri);
 
                                     let label = label.clone();
                                     let loaded_graphs = state.loaded_graphs.clone();
                                     let fetchin_graphs = state.fetching_graphs.clone();
+                                    let show_changepoints = state.show_changepoints.clone();
+                                    let loaded_changepoints = state.loaded_changepoints.clone();
+                                    let show_periodogram = state.show_periodogram.clone();
+                                    let selected = state.graphs.clone();
+                                    let periodograms = state.periodograms.clone();
                                     ehttp::fetch(
                                         request,
                                         move |result: ehttp::Result<ehttp::Response>| match result {
                                             Ok(v) if v.status == 200 => {
-                                                let graph: GraphData =
-                                                    from_bytes(&v.bytes).unwrap();
-                                                fetchin_graphs.lock().unwrap().remove(&label);
-                                                loaded_graphs.lock().unwrap().insert(label, graph);
+                                                // Queued before it stops being fetched, so that
+                                                // change points waiting for it go after it
+                                                let mut fetching = fetchin_graphs.lock().unwrap();
+                                                let Some(graph) =
+                                                    decode::<GraphData>("graph", &v.bytes)
+                                                else {
+                                                    fetching.remove(&label);
+                                                    return;
+                                                };
+                                                loaded_graphs
+                                                    .lock()
+                                                    .unwrap()
+                                                    .insert(label.clone(), graph);
+                                                fetching.remove(&label);
+                                                drop(fetching);
+                                                if show_changepoints.load(Ordering::SeqCst) {
+                                                    fetch_changepoints(&label, loaded_changepoints);
+                                                }
+                                                if show_periodogram.load(Ordering::SeqCst) {
+                                                    fetch_periodogram(
+                                                        &label,
+                                                        selected,
+                                                        periodograms,
+                                                    );
+                                                }
+                                            }
+                                            result => {
+                                                tracing::warn!(
+                                                    "error loading graph {}: {}",
+                                                    label,
+                                                    failure(&result)
+                                                );
                                             }
-                                            _ => {}
                                         },
                                     );
                                 } else {
                                     graphs.remove(&label);
+                                    update_location(graphs.keys());
+                                    state.periodograms.lock().unwrap().remove(&label);
                                     state.unloaded_graphs.lock().unwrap().push(label.clone());
                                 }
                             }
                         });
                     }
                 });
+
+                let mut show_changepoints = state.show_changepoints.load(Ordering::SeqCst);
+                if ui
+                    .checkbox(&mut show_changepoints, "Change points")
+                    .on_hover_text("Mark where the level or rate of each graph shifts")
+                    .clicked()
+                {
+                    state
+                        .show_changepoints
+                        .store(show_changepoints, Ordering::SeqCst);
+                    if show_changepoints {
+                        for name in graphs.lock().unwrap().keys() {
+                            fetch_changepoints(name, state.loaded_changepoints.clone());
+                        }
+                    } else {
+                        changepoints_removed_events.write(EventChangePointsRemoved);
+                    }
+                }
+
+                let mut show_periodogram = state.show_periodogram.load(Ordering::SeqCst);
+                if ui
+                    .checkbox(&mut show_periodogram, "Periodogram")
+                    .on_hover_text("Show the spectral power of each graph by period")
+                    .clicked()
+                {
+                    state
+                        .show_periodogram
+                        .store(show_periodogram, Ordering::SeqCst);
+                    if show_periodogram {
+                        for name in graphs.lock().unwrap().keys() {
+                            fetch_periodogram(
+                                name,
+                                state.graphs.clone(),
+                                state.periodograms.clone(),
+                            );
+                        }
+                    } else {
+                        state.periodograms.lock().unwrap().clear();
+                    }
+                }
             });
     }
 
-    let mut loaded_graphs = state.loaded_graphs.lock().unwrap();
-    for (name, graph) in loaded_graphs.iter() {
+    if state.show_periodogram.load(Ordering::SeqCst) {
+        let periodograms = state.periodograms.lock().unwrap();
+        if !periodograms.is_empty() {
+            periodogram_window(egui_context.ctx_mut().unwrap(), &periodograms);
+        }
+    }
+
+    for (name, graph) in state.loaded_graphs.lock().unwrap().drain() {
         added_events.write(EventGraphAdded {
-            graph_name: name.to_string(),
-            graph: graph.clone(),
+            graph_name: name,
+            graph,
         });
     }
-    loaded_graphs.clear();
 
     let mut unloaded_graphs = state.unloaded_graphs.lock().unwrap();
     for graph_name in unloaded_graphs.iter() {
@@ -260,6 +658,23 @@ fn ui(
         });
     }
     unloaded_graphs.clear();
+
+    // Change points for a graph that is still being fetched wait for it, and those for one that
+    // was unticked meanwhile are dropped
+    let graphs = state.graphs.lock().unwrap();
+    let fetching = state.fetching_graphs.lock().unwrap();
+    let mut loaded_changepoints = state.loaded_changepoints.lock().unwrap();
+    loaded_changepoints.retain(|name, _| graphs.contains_key(name));
+    let ready: Vec<String> = loaded_changepoints
+        .keys()
+        .filter(|name| !fetching.contains_key(*name))
+        .cloned()
+        .collect();
+    for name in ready {
+        if let Some(changepoints) = loaded_changepoints.remove(&name) {
+            changepoints_added_events.write(EventChangePointsAdded { changepoints });
+        }
+    }
 }
 
 fn date_scale(date: &NaiveDate) -> f32 {
@@ -341,6 +756,60 @@ fn graph_removed_listener(
     }
 }
 
+fn changepoints_added_listener(
+    mut events: EventReader<EventChangePointsAdded>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    graphs: Query<(&GraphName, &GraphPoints)>,
+) {
+    for event in events.read() {
+        // The graph may have been unticked while its change points were in flight
+        let Some((_, points)) = graphs
+            .iter()
+            .find(|(name, _)| name.0 == event.changepoints.name)
+        else {
+            continue;
+        };
+
+        let (min_y, max_y) = points
+            .0
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), (_, y)| {
+                (min.min(*y), max.max(*y))
+            });
+        let lines = event
+            .changepoints
+            .breakpoints
+            .iter()
+            .map(|date| {
+                let x = date_scale(date);
+                (Vec3::new(x, min_y, 0.0), Vec3::new(x, max_y, 0.0))
+            })
+            .collect();
+
+        commands.spawn((
+            Mesh2d(meshes.add(Mesh::from(Annotations { lines }))),
+            MeshMaterial2d(materials.add(Color::srgb_u8(0x8E, 0x83, 0x58))),
+            RenderLayers::layer(0),
+            GraphName(event.changepoints.name.to_string()),
+            ChangePointMarker,
+        ));
+    }
+}
+
+fn changepoints_removed_listener(
+    mut events: EventReader<EventChangePointsRemoved>,
+    mut commands: Commands,
+    markers: Query<Entity, With<ChangePointMarker>>,
+) {
+    for _ in events.read() {
+        for marker in markers.iter() {
+            commands.entity(marker).despawn();
+        }
+    }
+}
+
 #[derive(Component)]
 struct SceneCamera;
 
@@ -668,6 +1137,34 @@ impl From<LineGraph> for Mesh {
     }
 }
 
+/// Free standing line segments, such as the vertical markers at change points.
+#[derive(Debug, Clone)]
+pub struct Annotations {
+    pub lines: Vec<(Vec3, Vec3)>,
+}
+
+impl From<Annotations> for Mesh {
+    fn from(annotations: Annotations) -> Self {
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        let mut uvs = vec![];
+        for (start, end) in annotations.lines {
+            vertices.push(start.to_array());
+            vertices.push(end.to_array());
+        }
+        vertices.iter().for_each(|_| {
+            normals.push(Vec3::ZERO.to_array());
+            uvs.push([0.0; 2]);
+        });
+
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::default());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh
+    }
+}
+
 fn on_mousewheel(
     mut event_reader: EventReader<MouseWheel>,
     mut cameras: Query<&mut Transform, With<SceneCamera>>,
@@ -735,16 +1232,14 @@ fn on_mousemotion(
             });
 
             if let Some(((index, _, Vec2 { x: px, y: py }), labels, _name)) = closest_point {
-                if let Ok(highlighted_position) = camera
-                    .world_to_viewport(
-                        &camera_global_transform,
-                        Vec3 {
-                            x: px,
-                            y: py,
-                            z: 0.0,
-                        },
-                    )
-                {
+                if let Ok(highlighted_position) = camera.world_to_viewport(
+                    &camera_global_transform,
+                    Vec3 {
+                        x: px,
+                        y: py,
+                        z: 0.0,
+                    },
+                ) {
                     *visibility = Visibility::Visible;
 
                     position.translation.x = highlighted_position.x - window.width() / 2.0;